@@ -0,0 +1,65 @@
+/*
+ * Volume envelope (NRx2), stepped by the 64 Hz frame sequencer step.
+ */
+pub(super) struct Envelope {
+    register: u8,
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    pub(super) fn new() -> Self {
+        Self {
+            register: 0,
+            volume: 0,
+            timer: 0,
+        }
+    }
+
+    pub(super) fn get(&self) -> u8 {
+        self.register
+    }
+
+    pub(super) fn set(&mut self, byte: u8) {
+        self.register = byte;
+    }
+
+    /*
+     * The channel's DAC is powered whenever the initial volume or direction bits are set.
+     */
+    pub(super) fn dac_enabled(&self) -> bool {
+        self.register & 0xF8 != 0
+    }
+
+    pub(super) fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    pub(super) fn trigger(&mut self) {
+        self.volume = self.register >> 4;
+        self.timer = self.pace();
+    }
+
+    pub(super) fn clock(&mut self) {
+        if self.pace() == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = self.pace();
+
+        if self.register & 0b1000 != 0 {
+            if self.volume < 15 {
+                self.volume += 1;
+            }
+        } else if self.volume > 0 {
+            self.volume -= 1;
+        }
+    }
+
+    fn pace(&self) -> u8 {
+        self.register & 0b111
+    }
+}
@@ -0,0 +1,291 @@
+mod envelope;
+mod length;
+mod noise;
+mod pulse;
+mod wave;
+
+use noise::Noise;
+use pulse::Pulse;
+use wave::{Wave, WAVE_RAM_SIZE};
+
+// Dots per second, which the APU runs at regardless of CGB double-speed mode.
+const CLOCK_RATE: u64 = 4_194_304;
+
+// Keep at most a second of samples when nobody is draining them.
+const MAX_BUFFERED_SECONDS: usize = 1;
+
+pub(crate) const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+pub(crate) struct Apu {
+    powered: bool,
+    channel_1: Pulse,
+    channel_2: Pulse,
+    channel_3: Wave,
+    channel_4: Noise,
+    master_volume: u8,
+    panning: u8,
+    frame_step: u8,
+
+    sample_rate: u32,
+    sample_clock: u64,
+    charge_factor: f32,
+    capacitor: (f32, f32),
+    samples: Vec<f32>,
+    // Mono output of each channel on its own, only collected while a per-channel recording is running.
+    channel_samples: Option<[Vec<f32>; 4]>,
+}
+
+impl Apu {
+    pub(crate) fn new(sample_rate: u32) -> Self {
+        Self {
+            powered: true,
+            channel_1: Pulse::new(true),
+            channel_2: Pulse::new(false),
+            channel_3: Wave::new(),
+            channel_4: Noise::new(),
+            master_volume: 0x77,
+            panning: 0xF3,
+            frame_step: 0,
+            sample_rate,
+            sample_clock: 0,
+            charge_factor: charge_factor(sample_rate),
+            capacitor: (0.0, 0.0),
+            samples: Vec::new(),
+            channel_samples: None,
+        }
+    }
+
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.charge_factor = charge_factor(sample_rate);
+    }
+
+    /*
+     * Interleaved stereo samples (left, right) in -1.0..=1.0 produced since the last call.
+     */
+    pub(crate) fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    pub(crate) fn set_channel_capture(&mut self, enabled: bool) {
+        self.channel_samples = enabled.then(Default::default);
+    }
+
+    /*
+     * Mono samples of each channel before panning and master volume, produced since the last call.
+     * Empty unless channel capture is enabled.
+     */
+    pub(crate) fn take_channel_samples(&mut self) -> [Vec<f32>; 4] {
+        match &mut self.channel_samples {
+            Some(channels) => channels.each_mut().map(std::mem::take),
+            None => Default::default(),
+        }
+    }
+
+    pub(crate) fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF10..=0xFF14 => self.channel_1.read(address - 0xFF10),
+            0xFF15..=0xFF19 => self.channel_2.read(address - 0xFF15),
+            0xFF1A..=0xFF1E => self.channel_3.read(address - 0xFF1A),
+            0xFF1F..=0xFF23 => self.channel_4.read(address - 0xFF1F),
+            0xFF24 => self.master_volume,
+            0xFF25 => self.panning,
+            0xFF26 => {
+                0x70 | ((self.powered as u8) << 7)
+                    | ((self.channel_4.is_enabled() as u8) << 3)
+                    | ((self.channel_3.is_enabled() as u8) << 2)
+                    | ((self.channel_2.is_enabled() as u8) << 1)
+                    | self.channel_1.is_enabled() as u8
+            }
+            0xFF27..=0xFF2F => 0xFF,
+            0xFF30..=0xFF3F => self.channel_3.read_ram(address - 0xFF30),
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn write(&mut self, address: u16, byte: u8) {
+        // While powered off, only NR52 and wave RAM are writable.
+        if !self.powered && !matches!(address, 0xFF26 | 0xFF30..=0xFF3F) {
+            return;
+        }
+
+        match address {
+            0xFF10..=0xFF14 => self.channel_1.write(address - 0xFF10, byte),
+            0xFF15..=0xFF19 => self.channel_2.write(address - 0xFF15, byte),
+            0xFF1A..=0xFF1E => self.channel_3.write(address - 0xFF1A, byte),
+            0xFF1F..=0xFF23 => self.channel_4.write(address - 0xFF1F, byte),
+            0xFF24 => self.master_volume = byte,
+            0xFF25 => self.panning = byte,
+            0xFF26 => {
+                let powered = byte & 0x80 != 0;
+                if self.powered && !powered {
+                    self.power_off();
+                } else if !self.powered && powered {
+                    self.frame_step = 0;
+                }
+                self.powered = powered;
+            }
+            0xFF27..=0xFF2F => {}
+            0xFF30..=0xFF3F => self.channel_3.write_ram(address - 0xFF30, byte),
+            _ => unreachable!(),
+        }
+    }
+
+    /*
+     * Powering off clears every register apart from wave RAM.
+     */
+    fn power_off(&mut self) {
+        let mut wave_ram = [0; WAVE_RAM_SIZE];
+        for (offset, byte) in wave_ram.iter_mut().enumerate() {
+            *byte = self.channel_3.read_ram(offset as u16);
+        }
+
+        self.channel_1 = Pulse::new(true);
+        self.channel_2 = Pulse::new(false);
+        self.channel_3 = Wave::new();
+        self.channel_4 = Noise::new();
+        self.master_volume = 0;
+        self.panning = 0;
+
+        for (offset, byte) in wave_ram.into_iter().enumerate() {
+            self.channel_3.write_ram(offset as u16, byte);
+        }
+    }
+
+    /*
+     * Advance the 512 Hz frame sequencer, clocked by a falling edge of DIV bit 4 (bit 5 in double-speed mode).
+     * Length timers step at 256 Hz, the sweep at 128 Hz and envelopes at 64 Hz.
+     */
+    pub(crate) fn clock_frame_sequencer(&mut self) {
+        if !self.powered {
+            return;
+        }
+
+        if self.frame_step.is_multiple_of(2) {
+            self.channel_1.clock_length();
+            self.channel_2.clock_length();
+            self.channel_3.clock_length();
+            self.channel_4.clock_length();
+        }
+        if self.frame_step == 2 || self.frame_step == 6 {
+            self.channel_1.clock_sweep();
+        }
+        if self.frame_step == 7 {
+            self.channel_1.clock_envelope();
+            self.channel_2.clock_envelope();
+            self.channel_4.clock_envelope();
+        }
+        self.frame_step = (self.frame_step + 1) % 8;
+    }
+
+    /*
+     * Advance the channels by the given number of dots, emitting samples at the host sample rate.
+     */
+    pub(crate) fn tick(&mut self, dots: u8) {
+        for _ in 0..dots {
+            if self.powered {
+                self.channel_1.tick();
+                self.channel_2.tick();
+                self.channel_3.tick();
+                self.channel_4.tick();
+            }
+
+            self.sample_clock += self.sample_rate as u64;
+            if self.sample_clock >= CLOCK_RATE {
+                self.sample_clock -= CLOCK_RATE;
+                self.push_sample();
+            }
+        }
+    }
+
+    fn push_sample(&mut self) {
+        let limit = self.sample_rate as usize * 2 * MAX_BUFFERED_SECONDS;
+        if self.samples.len() >= limit {
+            self.samples.drain(..limit / 2);
+        }
+
+        if let Some(channels) = &mut self.channel_samples {
+            let outputs = [
+                self.channel_1.output(),
+                self.channel_2.output(),
+                self.channel_3.output(),
+                self.channel_4.output(),
+            ];
+            for (samples, output) in channels.iter_mut().zip(outputs) {
+                if samples.len() >= limit / 2 {
+                    samples.drain(..limit / 4);
+                }
+                let powered = self.powered.then_some(output).flatten();
+                samples.push(powered.map_or(0.0, dac));
+            }
+        }
+
+        let (left, right) = self.mix();
+        let left = self.high_pass(left, true);
+        let right = self.high_pass(right, false);
+        self.samples.push(left);
+        self.samples.push(right);
+    }
+
+    /*
+     * The output capacitor removes the DC offset left by DACs that are on but silent.
+     */
+    fn high_pass(&mut self, input: f32, left: bool) -> f32 {
+        let capacitor = if left {
+            &mut self.capacitor.0
+        } else {
+            &mut self.capacitor.1
+        };
+        let output = input - *capacitor;
+        *capacitor = input - output * self.charge_factor;
+        output
+    }
+
+    /*
+     * Mix the channels according to NR51 panning and NR50 master volume.
+     */
+    fn mix(&self) -> (f32, f32) {
+        if !self.powered {
+            return (0.0, 0.0);
+        }
+
+        let outputs = [
+            self.channel_1.output(),
+            self.channel_2.output(),
+            self.channel_3.output(),
+            self.channel_4.output(),
+        ];
+        let mut left = 0.0;
+        let mut right = 0.0;
+        for (channel, output) in outputs.into_iter().enumerate() {
+            let Some(output) = output else {
+                continue;
+            };
+            let analog = dac(output);
+            if self.panning & (0x10 << channel) != 0 {
+                left += analog;
+            }
+            if self.panning & (0x01 << channel) != 0 {
+                right += analog;
+            }
+        }
+
+        let left_volume = (((self.master_volume >> 4) & 0b111) + 1) as f32 / 8.0;
+        let right_volume = ((self.master_volume & 0b111) + 1) as f32 / 8.0;
+        (left / 4.0 * left_volume, right / 4.0 * right_volume)
+    }
+}
+
+/*
+ * Convert a digital channel output (0-15) to an analog level. DACs output -1.0 for 0 and +1.0 for 15.
+ */
+fn dac(output: u8) -> f32 {
+    output as f32 / 7.5 - 1.0
+}
+
+/*
+ * How much charge the output capacitor keeps between two samples at the given rate.
+ */
+fn charge_factor(sample_rate: u32) -> f32 {
+    0.999958f32.powf(CLOCK_RATE as f32 / sample_rate as f32)
+}
@@ -0,0 +1,115 @@
+use super::envelope::Envelope;
+use super::length::LengthCounter;
+
+/*
+ * Noise channel, driven by a 15-bit (or 7-bit) linear feedback shift register.
+ */
+pub(super) struct Noise {
+    enabled: bool,
+    register: u8,
+    timer: u32,
+    lfsr: u16,
+    length: LengthCounter,
+    envelope: Envelope,
+}
+
+impl Noise {
+    pub(super) fn new() -> Self {
+        Self {
+            enabled: false,
+            register: 0,
+            timer: 0,
+            lfsr: 0,
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+        }
+    }
+
+    pub(super) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(super) fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => 0xFF,
+            1 => 0xFF,
+            2 => self.envelope.get(),
+            3 => self.register,
+            4 => 0xBF | ((self.length.is_enabled() as u8) << 6),
+            _ => unreachable!(),
+        }
+    }
+
+    pub(super) fn write(&mut self, register: u16, byte: u8) {
+        match register {
+            0 => {}
+            1 => self.length.load((byte & 0x3F) as u16),
+            2 => {
+                self.envelope.set(byte);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.register = byte,
+            4 => {
+                self.length.set_enabled(byte & 0x40 != 0);
+                if byte & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.timer = self.period();
+        self.lfsr = 0;
+        self.length.trigger();
+        self.envelope.trigger();
+    }
+
+    fn period(&self) -> u32 {
+        let divisor = match self.register & 0b111 {
+            0 => 8,
+            code => code as u32 * 16,
+        };
+        divisor << (self.register >> 4)
+    }
+
+    pub(super) fn tick(&mut self) {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = self.period();
+
+        let feedback = !(self.lfsr ^ (self.lfsr >> 1)) & 1;
+        self.lfsr = (self.lfsr & !(1 << 15)) | (feedback << 15);
+        if self.register & 0b1000 != 0 {
+            // 7-bit mode also feeds back into bit 7
+            self.lfsr = (self.lfsr & !(1 << 7)) | (feedback << 7);
+        }
+        self.lfsr >>= 1;
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if !self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub(super) fn output(&self) -> Option<u8> {
+        if !self.envelope.dac_enabled() {
+            return None;
+        }
+        if !self.enabled {
+            return Some(0);
+        }
+        Some((self.lfsr as u8 & 1) * self.envelope.volume())
+    }
+}
@@ -0,0 +1,206 @@
+use super::envelope::Envelope;
+use super::length::LengthCounter;
+
+const DUTY_CYCLES: [[u8; 8]; 4] = [
+    [0, 0, 0, 0, 0, 0, 0, 1], // 12.5%
+    [1, 0, 0, 0, 0, 0, 0, 1], // 25%
+    [1, 0, 0, 0, 0, 1, 1, 1], // 50%
+    [0, 1, 1, 1, 1, 1, 1, 0], // 75%
+];
+
+/*
+ * Frequency sweep (NR10), only present on channel 1. Stepped by the 128 Hz frame sequencer step.
+ */
+struct Sweep {
+    register: u8,
+    enabled: bool,
+    shadow: u16,
+    timer: u8,
+}
+
+impl Sweep {
+    fn pace(&self) -> u8 {
+        (self.register >> 4) & 0b111
+    }
+
+    fn step(&self) -> u8 {
+        self.register & 0b111
+    }
+
+    fn reload_timer(&mut self) {
+        self.timer = if self.pace() == 0 { 8 } else { self.pace() };
+    }
+
+    /*
+     * Next frequency from the shadow register. Values above 2047 overflow and disable the channel.
+     */
+    fn next_frequency(&self) -> u16 {
+        let delta = self.shadow >> self.step();
+        if self.register & 0b1000 != 0 {
+            self.shadow.wrapping_sub(delta)
+        } else {
+            self.shadow + delta
+        }
+    }
+}
+
+/*
+ * Square wave channel. Channel 1 has a sweep unit, channel 2 does not.
+ */
+pub(super) struct Pulse {
+    enabled: bool,
+    duty: u8,
+    duty_step: u8,
+    frequency: u16,
+    timer: u16,
+    length: LengthCounter,
+    envelope: Envelope,
+    sweep: Option<Sweep>,
+}
+
+impl Pulse {
+    pub(super) fn new(with_sweep: bool) -> Self {
+        Self {
+            enabled: false,
+            duty: 0,
+            duty_step: 0,
+            frequency: 0,
+            timer: 0,
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+            sweep: with_sweep.then_some(Sweep {
+                register: 0,
+                enabled: false,
+                shadow: 0,
+                timer: 0,
+            }),
+        }
+    }
+
+    pub(super) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /*
+     * Read NRx0-NRx4, with write-only bits reading back as 1.
+     */
+    pub(super) fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => match &self.sweep {
+                Some(sweep) => 0x80 | sweep.register,
+                None => 0xFF,
+            },
+            1 => 0x3F | (self.duty << 6),
+            2 => self.envelope.get(),
+            3 => 0xFF,
+            4 => 0xBF | ((self.length.is_enabled() as u8) << 6),
+            _ => unreachable!(),
+        }
+    }
+
+    pub(super) fn write(&mut self, register: u16, byte: u8) {
+        match register {
+            0 => {
+                if let Some(sweep) = &mut self.sweep {
+                    sweep.register = byte & 0x7F;
+                }
+            }
+            1 => {
+                self.duty = byte >> 6;
+                self.length.load((byte & 0x3F) as u16);
+            }
+            2 => {
+                self.envelope.set(byte);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.frequency = (self.frequency & 0x700) | byte as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | ((byte as u16 & 0b111) << 8);
+                self.length.set_enabled(byte & 0x40 != 0);
+                if byte & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.timer = self.period();
+        self.length.trigger();
+        self.envelope.trigger();
+
+        if let Some(sweep) = &mut self.sweep {
+            sweep.shadow = self.frequency;
+            sweep.reload_timer();
+            sweep.enabled = sweep.pace() != 0 || sweep.step() != 0;
+            if sweep.step() != 0 && sweep.next_frequency() > 2047 {
+                self.enabled = false;
+            }
+        }
+    }
+
+    fn period(&self) -> u16 {
+        (2048 - self.frequency) * 4
+    }
+
+    pub(super) fn tick(&mut self) {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = self.period();
+            self.duty_step = (self.duty_step + 1) % 8;
+        }
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if !self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub(super) fn clock_sweep(&mut self) {
+        let Some(sweep) = &mut self.sweep else {
+            return;
+        };
+        sweep.timer = sweep.timer.saturating_sub(1);
+        if sweep.timer > 0 {
+            return;
+        }
+        sweep.reload_timer();
+        if !sweep.enabled || sweep.pace() == 0 {
+            return;
+        }
+
+        let frequency = sweep.next_frequency();
+        if frequency > 2047 {
+            self.enabled = false;
+        } else if sweep.step() != 0 {
+            sweep.shadow = frequency;
+            self.frequency = frequency;
+            // The new value is checked for overflow again straight away, but not written back.
+            if sweep.next_frequency() > 2047 {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /*
+     * Current DAC input (0-15).
+     */
+    pub(super) fn output(&self) -> Option<u8> {
+        if !self.envelope.dac_enabled() {
+            return None;
+        }
+        if !self.enabled {
+            return Some(0);
+        }
+        Some(DUTY_CYCLES[self.duty as usize][self.duty_step as usize] * self.envelope.volume())
+    }
+}
@@ -0,0 +1,122 @@
+use super::length::LengthCounter;
+
+pub(super) const WAVE_RAM_SIZE: usize = 16;
+
+/*
+ * Wave channel, playing back the 32 4-bit samples held in wave RAM.
+ */
+pub(super) struct Wave {
+    enabled: bool,
+    dac_enabled: bool,
+    output_level: u8,
+    frequency: u16,
+    timer: u16,
+    position: u8,
+    length: LengthCounter,
+    ram: [u8; WAVE_RAM_SIZE],
+}
+
+impl Wave {
+    pub(super) fn new() -> Self {
+        Self {
+            enabled: false,
+            dac_enabled: false,
+            output_level: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            length: LengthCounter::new(256),
+            ram: [0; WAVE_RAM_SIZE],
+        }
+    }
+
+    pub(super) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(super) fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => 0x7F | ((self.dac_enabled as u8) << 7),
+            1 => 0xFF,
+            2 => 0x9F | (self.output_level << 5),
+            3 => 0xFF,
+            4 => 0xBF | ((self.length.is_enabled() as u8) << 6),
+            _ => unreachable!(),
+        }
+    }
+
+    pub(super) fn write(&mut self, register: u16, byte: u8) {
+        match register {
+            0 => {
+                self.dac_enabled = byte & 0x80 != 0;
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            1 => self.length.load(byte as u16),
+            2 => self.output_level = (byte >> 5) & 0b11,
+            3 => self.frequency = (self.frequency & 0x700) | byte as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | ((byte as u16 & 0b111) << 8);
+                self.length.set_enabled(byte & 0x40 != 0);
+                if byte & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    pub(super) fn read_ram(&self, offset: u16) -> u8 {
+        self.ram[offset as usize]
+    }
+
+    pub(super) fn write_ram(&mut self, offset: u16, byte: u8) {
+        self.ram[offset as usize] = byte;
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.timer = self.period();
+        self.position = 0;
+        self.length.trigger();
+    }
+
+    fn period(&self) -> u16 {
+        (2048 - self.frequency) * 2
+    }
+
+    pub(super) fn tick(&mut self) {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = self.period();
+            self.position = (self.position + 1) % 32;
+        }
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if !self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn output(&self) -> Option<u8> {
+        if !self.dac_enabled {
+            return None;
+        }
+        if !self.enabled {
+            return Some(0);
+        }
+        let byte = self.ram[self.position as usize / 2];
+        // Upper nibble first
+        let sample = if self.position.is_multiple_of(2) {
+            byte >> 4
+        } else {
+            byte & 0xF
+        };
+        Some(match self.output_level {
+            0 => 0,
+            level => sample >> (level - 1),
+        })
+    }
+}
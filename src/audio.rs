@@ -0,0 +1,160 @@
+use crate::apu::DEFAULT_SAMPLE_RATE;
+
+#[cfg(feature = "audio")]
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+#[cfg(feature = "audio")]
+use std::collections::VecDeque;
+#[cfg(feature = "audio")]
+use std::sync::{Arc, Mutex};
+
+// Amount of audio to keep queued for the device, in seconds.
+#[cfg(feature = "audio")]
+const BUFFER_SECONDS: f32 = 0.1;
+
+// Largest adjustment made to the sample rate to steer the buffer back to half full.
+#[cfg(feature = "audio")]
+const MAX_RATE_DELTA: f32 = 0.005;
+
+/*
+ * Host audio output. The APU's interleaved stereo samples are queued in a ring buffer drained by the device callback.
+ * If no output device can be opened (or the audio feature is disabled), samples are dropped and the emulator runs
+ * silently.
+ */
+pub(crate) struct Audio {
+    #[cfg(feature = "audio")]
+    output: Option<Output>,
+}
+
+#[cfg(feature = "audio")]
+struct Output {
+    _stream: cpal::Stream,
+    buffer: Arc<Mutex<VecDeque<f32>>>,
+    sample_rate: u32,
+    capacity: usize,
+}
+
+impl Audio {
+    #[cfg(feature = "audio")]
+    pub(crate) fn open() -> Self {
+        let output = Output::open().unwrap_or_else(|e| {
+            eprintln!("Audio unavailable, running silently: {}", e);
+            None
+        });
+        Self { output }
+    }
+
+    #[cfg(not(feature = "audio"))]
+    pub(crate) fn open() -> Self {
+        Self {}
+    }
+
+    /*
+     * Sample rate the APU should produce at for the next frame.
+     * Dynamic rate control: the device rate is nudged up when the buffer is under half full and down when it is over,
+     * so the emulator's slightly different frame rate never runs the buffer dry or overflows it.
+     */
+    pub(crate) fn sample_rate(&self) -> u32 {
+        #[cfg(feature = "audio")]
+        if let Some(output) = &self.output {
+            let fill = output.buffer.lock().unwrap().len() as f32 / output.capacity as f32;
+            let ratio = 1.0 + MAX_RATE_DELTA * (1.0 - 2.0 * fill.min(1.0));
+            return (output.sample_rate as f32 * ratio) as u32;
+        }
+        DEFAULT_SAMPLE_RATE
+    }
+
+    /*
+     * The device's own sample rate, without rate control. Used while recording so captures are reproducible.
+     */
+    pub(crate) fn nominal_sample_rate(&self) -> u32 {
+        #[cfg(feature = "audio")]
+        if let Some(output) = &self.output {
+            return output.sample_rate;
+        }
+        DEFAULT_SAMPLE_RATE
+    }
+
+    pub(crate) fn queue(&mut self, samples: &[f32]) {
+        #[cfg(feature = "audio")]
+        if let Some(output) = &self.output {
+            let mut buffer = output.buffer.lock().unwrap();
+            // Drop whatever doesn't fit rather than letting latency grow.
+            let space = output.capacity.saturating_sub(buffer.len()) & !1;
+            buffer.extend(&samples[..samples.len().min(space)]);
+        }
+        #[cfg(not(feature = "audio"))]
+        let _ = samples;
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Output {
+    /*
+     * Open the default output device. Returns None if the host has no output device.
+     */
+    fn open() -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let Some(device) = cpal::default_host().default_output_device() else {
+            return Ok(None);
+        };
+        let supported = device.default_output_config()?;
+        let config: cpal::StreamConfig = supported.config();
+        let sample_rate = config.sample_rate.0;
+        let capacity = (sample_rate as f32 * BUFFER_SECONDS) as usize * 2;
+        let buffer = Arc::new(Mutex::new(VecDeque::with_capacity(capacity)));
+
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => Self::build_stream::<f32>(&device, &config, &buffer)?,
+            cpal::SampleFormat::I16 => Self::build_stream::<i16>(&device, &config, &buffer)?,
+            cpal::SampleFormat::U16 => Self::build_stream::<u16>(&device, &config, &buffer)?,
+            format => return Err(format!("unsupported sample format {}", format).into()),
+        };
+        stream.play()?;
+
+        Ok(Some(Self {
+            _stream: stream,
+            buffer,
+            sample_rate,
+            capacity,
+        }))
+    }
+
+    fn build_stream<T>(
+        device: &cpal::Device,
+        config: &cpal::StreamConfig,
+        buffer: &Arc<Mutex<VecDeque<f32>>>,
+    ) -> Result<cpal::Stream, cpal::BuildStreamError>
+    where
+        T: cpal::SizedSample + cpal::FromSample<f32>,
+    {
+        let channels = config.channels as usize;
+        let buffer = Arc::clone(buffer);
+        // Repeat the last frame on underrun to avoid a click back to zero.
+        let mut last = (0.0, 0.0);
+
+        device.build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                let mut buffer = buffer.lock().unwrap();
+                for frame in data.chunks_mut(channels) {
+                    if buffer.len() >= 2 {
+                        last = (buffer.pop_front().unwrap(), buffer.pop_front().unwrap());
+                    }
+                    let (left, right) = last;
+                    match frame {
+                        [mono] => *mono = T::from_sample((left + right) / 2.0),
+                        [l, r, rest @ ..] => {
+                            *l = T::from_sample(left);
+                            *r = T::from_sample(right);
+                            for sample in rest {
+                                *sample = T::from_sample(0.0);
+                            }
+                        }
+                        [] => {}
+                    }
+                }
+            },
+            |e| eprintln!("Audio stream error: {}", e),
+            None,
+        )
+    }
+}
@@ -0,0 +1,251 @@
+use super::image_source::{ImageSource, TestPattern, IMAGE_HEIGHT, IMAGE_WIDTH};
+use super::*;
+
+// Registers, mirrored every 0x80 bytes of 0xA000-0xBFFF while selected.
+const REGISTER_COUNT: usize = 0x36;
+const CONTROL: usize = 0x00;
+const GAIN: usize = 0x01;
+const EXPOSURE_HIGH: usize = 0x02;
+const EXPOSURE_LOW: usize = 0x03;
+const EDGE: usize = 0x04;
+const DITHER_MATRIX: usize = 0x06;
+
+const CONTROL_CAPTURE: u8 = 0b0000_0001;
+const GAIN_N: u8 = 0b1000_0000;
+const GAIN_VH: u8 = 0b0110_0000;
+const EDGE_INVERT: u8 = 0b0000_1000;
+
+// Every Pocket Camera has 128 KiB of RAM, whatever the header says, and captures assume it is all there.
+const RAM_SIZE: usize = 128 * 1024;
+
+// The captured image is stored as tiles in RAM bank 0 from this offset.
+const IMAGE_OFFSET: usize = 0x100;
+
+// Edge enhancement strength selected by bits 4-6 of the edge register.
+const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+// Exposure at which a source pixel comes through unchanged, before gain.
+const NEUTRAL_EXPOSURE: f32 = 0x0800 as f32;
+
+/*
+ * The Pocket Camera mapper: MBC-style ROM and RAM banking plus the M64282FP sensor.
+ * Setting bit 4 of the RAM bank register maps the sensor registers over 0xA000-0xBFFF. Starting a capture runs the
+ * source image through exposure and gain, edge enhancement and the 4x4 dithering matrix, then writes the resulting
+ * 2-bit tiles into RAM once the exposure time has elapsed.
+ * The analog stages are approximations tuned to give sensible photos rather than a model of the sensor's circuitry.
+ */
+pub(super) struct Camera {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    dirty: Dirty,
+    source: Box<dyn ImageSource>,
+
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    registers_selected: bool,
+    registers: [u8; REGISTER_COUNT],
+    // T-cycles until the capture in progress completes.
+    capture_cycles: u32,
+}
+
+impl Camera {
+    pub(super) fn new(rom: Vec<u8>) -> Self {
+        Self {
+            rom,
+            ram: vec![0; RAM_SIZE],
+            dirty: Dirty::default(),
+            source: Box::new(TestPattern::new()),
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            registers_selected: false,
+            registers: [0; REGISTER_COUNT],
+            capture_cycles: 0,
+        }
+    }
+
+    fn exposure(&self) -> u16 {
+        ((self.registers[EXPOSURE_HIGH] as u16) << 8) | self.registers[EXPOSURE_LOW] as u16
+    }
+
+    /*
+     * Capture time in T-cycles, which grows with the exposure.
+     */
+    fn capture_duration(&self) -> u32 {
+        let n = if self.registers[GAIN] & GAIN_N != 0 {
+            0
+        } else {
+            512
+        };
+        (32446 + n + 16 * self.exposure() as u32) * 4
+    }
+
+    fn capture(&mut self) {
+        let image = self.source.capture();
+        let exposure = self.exposure() as f32 / NEUTRAL_EXPOSURE;
+        let gain = 1.0 + (self.registers[GAIN] & 0x1F) as f32 / 16.0;
+        let invert = self.registers[EDGE] & EDGE_INVERT != 0;
+
+        let levels: Vec<f32> = image
+            .iter()
+            .map(|&pixel| {
+                let level = pixel as f32 * exposure * gain;
+                if invert {
+                    255.0 - level
+                } else {
+                    level
+                }
+            })
+            .collect();
+        let levels = self.enhance_edges(&levels);
+
+        for y in 0..IMAGE_HEIGHT {
+            for x in 0..IMAGE_WIDTH {
+                let color = self.dither(x, y, levels[y * IMAGE_WIDTH + x]);
+                let tile = (y / 8) * (IMAGE_WIDTH / 8) + x / 8;
+                let address = IMAGE_OFFSET + tile * 16 + (y % 8) * 2;
+                let bit = 7 - (x % 8);
+                for (plane, shift) in [(0, 0), (1, 1)] {
+                    let byte = &mut self.ram[address + plane];
+                    *byte = (*byte & !(1 << bit)) | (((color >> shift) & 1) << bit);
+                }
+            }
+        }
+        self.dirty.mark(true);
+    }
+
+    /*
+     * Sharpen by the selected ratio against the horizontal and/or vertical neighbours, as chosen by the VH bits.
+     */
+    fn enhance_edges(&self, levels: &[f32]) -> Vec<f32> {
+        let vh = (self.registers[GAIN] & GAIN_VH) >> 5;
+        if vh == 0 {
+            return levels.to_vec();
+        }
+        let ratio = EDGE_RATIOS[((self.registers[EDGE] >> 4) & 0b111) as usize];
+        let at = |x: usize, y: usize| {
+            levels[y.min(IMAGE_HEIGHT - 1) * IMAGE_WIDTH + x.min(IMAGE_WIDTH - 1)]
+        };
+
+        let mut enhanced = Vec::with_capacity(levels.len());
+        for y in 0..IMAGE_HEIGHT {
+            for x in 0..IMAGE_WIDTH {
+                let center = at(x, y);
+                let mut edge = 0.0;
+                if vh & 0b10 != 0 {
+                    edge += 2.0 * center - at(x.saturating_sub(1), y) - at(x + 1, y);
+                }
+                if vh & 0b01 != 0 {
+                    edge += 2.0 * center - at(x, y.saturating_sub(1)) - at(x, y + 1);
+                }
+                enhanced.push(center + edge * ratio);
+            }
+        }
+        enhanced
+    }
+
+    /*
+     * Quantise a level to a 2-bit colour (0 lightest) with the three thresholds for this position in the 4x4 matrix.
+     */
+    fn dither(&self, x: usize, y: usize, level: f32) -> u8 {
+        let index = DITHER_MATRIX + ((y % 4) * 4 + x % 4) * 3;
+        let thresholds = &self.registers[index..index + 3];
+        let level = level.clamp(0.0, 255.0) as u8;
+        match thresholds.iter().position(|&threshold| level < threshold) {
+            Some(0) => 3,
+            Some(1) => 2,
+            Some(_) => 1,
+            None => 0,
+        }
+    }
+}
+
+impl Mbc for Camera {
+    fn read_rom(&self, address: u16) -> u8 {
+        let offset = (address & 0x3FFF) as usize;
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, offset),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, offset),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = byte & 0x3F,
+            0x4000..=0x5FFF => {
+                self.registers_selected = byte & 0x10 != 0;
+                self.ram_bank = byte & 0x0F;
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.registers_selected {
+            // Only the capture bit of the control register can be read back.
+            return match (address & 0x7F) as usize {
+                CONTROL => self.registers[CONTROL] & CONTROL_CAPTURE,
+                _ => 0x00,
+            };
+        }
+        banked_read(
+            &self.ram,
+            RAM_BANK_SIZE,
+            self.ram_bank as usize,
+            (address & 0x1FFF) as usize,
+        )
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) {
+        if self.registers_selected {
+            let register = (address & 0x7F) as usize;
+            if register == CONTROL {
+                let starting = byte & CONTROL_CAPTURE != 0 && self.capture_cycles == 0;
+                self.registers[CONTROL] = byte & 0b111;
+                if starting {
+                    self.capture_cycles = self.capture_duration();
+                }
+            } else if register < REGISTER_COUNT {
+                self.registers[register] = byte;
+            }
+            return;
+        }
+        if self.ram_enabled {
+            self.dirty.mark(banked_write(
+                &mut self.ram,
+                RAM_BANK_SIZE,
+                self.ram_bank as usize,
+                (address & 0x1FFF) as usize,
+                byte,
+            ));
+        }
+    }
+
+    fn save(&self) -> Option<Vec<u8>> {
+        Some(self.ram.clone())
+    }
+
+    fn load(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.dirty.take()
+    }
+
+    fn tick(&mut self, t_cycles: u8) {
+        if self.capture_cycles == 0 {
+            return;
+        }
+        self.capture_cycles = self.capture_cycles.saturating_sub(t_cycles as u32);
+        if self.capture_cycles == 0 {
+            self.capture();
+            self.registers[CONTROL] &= !CONTROL_CAPTURE;
+        }
+    }
+
+    fn set_image_source(&mut self, source: Box<dyn ImageSource>) {
+        self.source = source;
+    }
+}
@@ -0,0 +1,217 @@
+use super::Dirty;
+
+// 2 Kbit, organised as 128 16-bit words.
+const WORDS: usize = 128;
+// Start bit excluded: 2 opcode bits and 8 address bits, of which the top one is ignored.
+const COMMAND_BITS: u8 = 10;
+
+#[derive(Clone, Copy)]
+enum State {
+    // Waiting for a start bit.
+    Idle,
+    Command {
+        bits: u8,
+        shift: u16,
+    },
+    Reading {
+        data: u16,
+        bits: u8,
+    },
+    Writing {
+        address: Option<u8>,
+        bits: u8,
+        shift: u16,
+    },
+}
+
+/*
+ * A 93LC56 serial EEPROM, driven through chip select, clock and data in, with data out read back. Bits are shifted in
+ * on rising clock edges while chip select is high, most significant first.
+ */
+pub(super) struct Eeprom {
+    words: [u16; WORDS],
+    write_enabled: bool,
+    state: State,
+    dirty: Dirty,
+
+    chip_select: bool,
+    clock: bool,
+    data_in: bool,
+    data_out: bool,
+}
+
+impl Eeprom {
+    pub(super) fn new() -> Self {
+        Self {
+            // Erased cells read back as 1s.
+            words: [0xFFFF; WORDS],
+            write_enabled: false,
+            state: State::Idle,
+            dirty: Dirty::default(),
+            chip_select: false,
+            clock: false,
+            data_in: false,
+            data_out: true,
+        }
+    }
+
+    /*
+     * Pin levels as seen through the MBC7 register: bit 7 CS, bit 6 CLK, bit 1 DI, bit 0 DO.
+     */
+    pub(super) fn read(&self) -> u8 {
+        ((self.chip_select as u8) << 7)
+            | ((self.clock as u8) << 6)
+            | ((self.data_in as u8) << 1)
+            | self.data_out as u8
+    }
+
+    pub(super) fn write(&mut self, byte: u8) {
+        let chip_select = byte & 0x80 != 0;
+        let clock = byte & 0x40 != 0;
+        self.data_in = byte & 0x02 != 0;
+
+        if !chip_select {
+            // Deselecting aborts any command in progress.
+            self.state = State::Idle;
+            self.data_out = true;
+        } else if !self.clock && clock {
+            self.clock_in(self.data_in);
+        }
+        self.chip_select = chip_select;
+        self.clock = clock;
+    }
+
+    fn clock_in(&mut self, bit: bool) {
+        self.state = match self.state {
+            State::Idle if bit => State::Command { bits: 0, shift: 0 },
+            State::Idle => State::Idle,
+            State::Command { bits, shift } => {
+                let shift = (shift << 1) | bit as u16;
+                if bits + 1 < COMMAND_BITS {
+                    State::Command {
+                        bits: bits + 1,
+                        shift,
+                    }
+                } else {
+                    self.execute(shift)
+                }
+            }
+            State::Reading { data, bits } => {
+                self.data_out = data & 0x8000 != 0;
+                if bits > 1 {
+                    State::Reading {
+                        data: data << 1,
+                        bits: bits - 1,
+                    }
+                } else {
+                    State::Idle
+                }
+            }
+            State::Writing {
+                address,
+                bits,
+                shift,
+            } => {
+                let shift = (shift << 1) | bit as u16;
+                if bits + 1 < 16 {
+                    State::Writing {
+                        address,
+                        bits: bits + 1,
+                        shift,
+                    }
+                } else {
+                    if self.write_enabled {
+                        let changed = match address {
+                            Some(address) => {
+                                std::mem::replace(&mut self.words[address as usize], shift) != shift
+                            }
+                            None => {
+                                self.words.fill(shift);
+                                true
+                            }
+                        };
+                        self.dirty.mark(changed);
+                    }
+                    self.data_out = true;
+                    State::Idle
+                }
+            }
+        };
+    }
+
+    fn execute(&mut self, command: u16) -> State {
+        let address = (command & 0x7F) as u8;
+        match command >> 8 {
+            // READ: a dummy 0 bit, then the word.
+            0b10 => {
+                self.data_out = false;
+                State::Reading {
+                    data: self.words[address as usize],
+                    bits: 16,
+                }
+            }
+            // WRITE
+            0b01 => State::Writing {
+                address: Some(address),
+                bits: 0,
+                shift: 0,
+            },
+            // ERASE
+            0b11 => {
+                if self.write_enabled {
+                    self.dirty.mark(
+                        std::mem::replace(&mut self.words[address as usize], 0xFFFF) != 0xFFFF,
+                    );
+                }
+                State::Idle
+            }
+            // The remaining commands are told apart by the top two address bits.
+            _ => match (command >> 6) & 0b11 {
+                // EWDS
+                0b00 => {
+                    self.write_enabled = false;
+                    State::Idle
+                }
+                // WRAL
+                0b01 => State::Writing {
+                    address: None,
+                    bits: 0,
+                    shift: 0,
+                },
+                // ERAL
+                0b10 => {
+                    if self.write_enabled {
+                        self.words.fill(0xFFFF);
+                        self.dirty.mark(true);
+                    }
+                    State::Idle
+                }
+                // EWEN
+                _ => {
+                    self.write_enabled = true;
+                    State::Idle
+                }
+            },
+        }
+    }
+
+    /*
+     * The contents as 256 bytes, each word stored little-endian.
+     */
+    pub(super) fn save(&self) -> Vec<u8> {
+        self.words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    pub(super) fn take_dirty(&mut self) -> bool {
+        self.dirty.take()
+    }
+
+    pub(super) fn load(&mut self, data: &[u8]) {
+        for (word, bytes) in self.words.iter_mut().zip(data.chunks_exact(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+    }
+}
@@ -1,8 +1,11 @@
-enum Licensee {
-    None,
-}
+use std::fmt;
+
+use super::licensee::{Licensee, USE_NEW_LICENSEE};
 
-enum CartridgeType {
+// Named after the type strings in the header documentation, acronyms and all.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum CartridgeType {
     ROMOnly,
     MBC1,
     MBC1RAM,
@@ -31,135 +34,390 @@ enum CartridgeType {
     BandaiTAMA5,
     HuC3,
     HuC1RAMBattery,
+    Unknown(u8),
+}
+
+impl CartridgeType {
+    pub(crate) fn from_byte(byte: u8) -> Option<Self> {
+        Some(match byte {
+            0x00 => Self::ROMOnly,
+            0x01 => Self::MBC1,
+            0x02 => Self::MBC1RAM,
+            0x03 => Self::MBC1RAMBattery,
+            0x05 => Self::MBC2,
+            0x06 => Self::MBC2Battery,
+            0x08 => Self::ROMRAM,
+            0x09 => Self::ROMRAMBattery,
+            0x0B => Self::MMM01,
+            0x0C => Self::MMM01RAM,
+            0x0D => Self::MMM01RAMBattery,
+            0x0F => Self::MBC3TimerBattery,
+            0x10 => Self::MBC3TimerRAMBattery,
+            0x11 => Self::MBC3,
+            0x12 => Self::MBC3RAM,
+            0x13 => Self::MBC3RAMBattery,
+            0x19 => Self::MBC5,
+            0x1A => Self::MBC5RAM,
+            0x1B => Self::MBC5RAMBattery,
+            0x1C => Self::MBC5Rumble,
+            0x1D => Self::MBC5RumbleRAM,
+            0x1E => Self::MBC5RumbleRAMBattery,
+            0x20 => Self::MBC6,
+            0x22 => Self::MBC7SensorRumbleRAMBattery,
+            0xFC => Self::PocketCamera,
+            0xFD => Self::BandaiTAMA5,
+            0xFE => Self::HuC3,
+            0xFF => Self::HuC1RAMBattery,
+            _ => return None,
+        })
+    }
 }
 
-enum DestinationCode {
+impl fmt::Display for CartridgeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::ROMOnly => "ROM ONLY",
+            Self::MBC1 => "MBC1",
+            Self::MBC1RAM => "MBC1+RAM",
+            Self::MBC1RAMBattery => "MBC1+RAM+BATTERY",
+            Self::MBC2 => "MBC2",
+            Self::MBC2Battery => "MBC2+BATTERY",
+            Self::ROMRAM => "ROM+RAM",
+            Self::ROMRAMBattery => "ROM+RAM+BATTERY",
+            Self::MMM01 => "MMM01",
+            Self::MMM01RAM => "MMM01+RAM",
+            Self::MMM01RAMBattery => "MMM01+RAM+BATTERY",
+            Self::MBC3TimerBattery => "MBC3+TIMER+BATTERY",
+            Self::MBC3TimerRAMBattery => "MBC3+TIMER+RAM+BATTERY",
+            Self::MBC3 => "MBC3",
+            Self::MBC3RAM => "MBC3+RAM",
+            Self::MBC3RAMBattery => "MBC3+RAM+BATTERY",
+            Self::MBC5 => "MBC5",
+            Self::MBC5RAM => "MBC5+RAM",
+            Self::MBC5RAMBattery => "MBC5+RAM+BATTERY",
+            Self::MBC5Rumble => "MBC5+RUMBLE",
+            Self::MBC5RumbleRAM => "MBC5+RUMBLE+RAM",
+            Self::MBC5RumbleRAMBattery => "MBC5+RUMBLE+RAM+BATTERY",
+            Self::MBC6 => "MBC6",
+            Self::MBC7SensorRumbleRAMBattery => "MBC7+SENSOR+RUMBLE+RAM+BATTERY",
+            Self::PocketCamera => "POCKET CAMERA",
+            Self::BandaiTAMA5 => "BANDAI TAMA5",
+            Self::HuC3 => "HuC3",
+            Self::HuC1RAMBattery => "HuC1+RAM+BATTERY",
+            Self::Unknown(_) => "unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum DestinationCode {
     Japanese,
     Overseas,
+    Unknown(u8),
 }
 
-struct header {
-    title: [u8; 16],
-    manufacturer_code: [u8; 4],
-    licensee: Licensee,
-    cgb: bool,
-    sgb: bool,
-    cartridge_type: CartridgeType,
-    rom_size: usize,
-    num_rom_banks: usize,
-    ram_size: usize,
-    destination_code: DestinationCode,
-    version_number: u8,
-}
-
-impl header {
-    fn new(rom: &[u8; 0x150]) -> Self {
-        if rom[0x14D] != checksum(rom) as u8 {
-            panic!("Header checksum failed");
+// The header ends just before the first instruction after the entry point's jump.
+pub(crate) const HEADER_END: usize = 0x150;
+
+// The bitmap the boot ROM compares against 0x104-0x133 before it will start a cartridge.
+pub(crate) const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+pub(crate) const LOGO_START: usize = 0x104;
+// The CGB boot ROM only checks the top half of the logo.
+const CGB_LOGO_CHECKED: usize = 24;
+
+/*
+ * A problem found while parsing the header. In lenient mode these are reported as warnings instead.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum HeaderError {
+    TooShort { length: usize },
+    HeaderChecksum { expected: u8, actual: u8 },
+    UnknownCartridgeType(u8),
+    UnknownRomSize(u8),
+    UnknownRamSize(u8),
+    UnknownDestination(u8),
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderError::TooShort { length } => write!(
+                f,
+                "ROM is {} bytes, too short to hold a header (${:04X} bytes)",
+                length, HEADER_END
+            ),
+            HeaderError::HeaderChecksum { expected, actual } => write!(
+                f,
+                "header checksum is ${:02X}, expected ${:02X}",
+                actual, expected
+            ),
+            HeaderError::UnknownCartridgeType(byte) => {
+                write!(f, "unknown cartridge type ${:02X}", byte)
+            }
+            HeaderError::UnknownRomSize(byte) => write!(f, "unknown ROM size ${:02X}", byte),
+            HeaderError::UnknownRamSize(byte) => write!(f, "unknown RAM size ${:02X}", byte),
+            HeaderError::UnknownDestination(byte) => {
+                write!(f, "unknown destination code ${:02X}", byte)
+            }
         }
+    }
+}
 
-        let mut title = [0u8; 16];
-        title.copy_from_slice(&rom[0x134..0x143]);
-        let mut manufacturer_code = [0u8; 4];
-        manufacturer_code.copy_from_slice(&rom[0x13F..0x142]);
-
-        let cartridge_type = match rom[0x147] {
-            0x00 => CartridgeType::ROMOnly,
-            0x01 => CartridgeType::MBC1,
-            0x02 => CartridgeType::MBC1RAM,
-            0x03 => CartridgeType::MBC1RAMBattery,
-            0x05 => CartridgeType::MBC2,
-            0x06 => CartridgeType::MBC2Battery,
-            0x08 => CartridgeType::ROMRAM,
-            0x09 => CartridgeType::ROMRAMBattery,
-            0x0B => CartridgeType::MMM01,
-            0x0C => CartridgeType::MMM01RAM,
-            0x0D => CartridgeType::MMM01RAMBattery,
-            0x0F => CartridgeType::MBC3TimerBattery,
-            0x10 => CartridgeType::MBC3TimerRAMBattery,
-            0x11 => CartridgeType::MBC3,
-            0x12 => CartridgeType::MBC3RAM,
-            0x13 => CartridgeType::MBC3RAMBattery,
-            0x19 => CartridgeType::MBC5,
-            0x1A => CartridgeType::MBC5RAM,
-            0x1B => CartridgeType::MBC5RAMBattery,
-            0x1C => CartridgeType::MBC5Rumble,
-            0x1D => CartridgeType::MBC5RumbleRAM,
-            0x1E => CartridgeType::MBC5RumbleRAMBattery,
-            0x20 => CartridgeType::MBC6,
-            0x22 => CartridgeType::MBC7SensorRumbleRAMBattery,
-            0xFC => CartridgeType::PocketCamera,
-            0xFD => CartridgeType::BandaiTAMA5,
-            0xFE => CartridgeType::HuC3,
-            0xFF => CartridgeType::HuC1RAMBattery,
-            _ => unimplemented!(),
-        };
+/*
+ * Integrity checks on the ROM that hardware doesn't enforce (or that lenient loading lets through), reported for
+ * information rather than refused.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Diagnostic {
+    // Offset of the first logo byte that differs from Nintendo's.
+    LogoMismatch { offset: usize },
+    GlobalChecksum { expected: u16, actual: u16 },
+}
 
-        let rom_size = match rom[0x148] {
-            0x00 => 32 * 1024,   // 32KiB
-            0x01 => 64 * 1024,   // 64KiB
-            0x02 => 128 * 1024,  // 128KiB
-            0x03 => 256 * 1024,  // 256KiB
-            0x04 => 512 * 1024,  // 512KiB
-            0x05 => 1024 * 1024, // 1MiB
-            0x06 => 2048 * 1024, // 2MiB
-            0x07 => 4096 * 1024, // 4MiB
-            0x52 => 1152 * 1024, // 1.1MiB
-            0x53 => 1280 * 1024, // 1.2MiB
-            0x54 => 1536 * 1024, // 1.5MiB
-            _ => unimplemented!(),
-        };
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::LogoMismatch { offset } => {
+                write!(f, "Nintendo logo differs from ${:04X}", offset)
+            }
+            Diagnostic::GlobalChecksum { expected, actual } => write!(
+                f,
+                "global checksum is ${:04X}, expected ${:04X}",
+                actual, expected
+            ),
+        }
+    }
+}
 
-        let num_rom_banks = match rom[0x148] {
-            0x00 => 2,
-            0x01 => 4,
-            0x02 => 8,
-            0x03 => 16,
-            0x04 => 32,
-            0x05 => 64,
-            0x06 => 128,
-            0x07 => 256,
-            0x52 => 72,
-            0x53 => 80,
-            0x54 => 96,
-            _ => unimplemented!(),
-        };
+pub(crate) struct Header {
+    pub(crate) title: [u8; 16],
+    pub(crate) manufacturer_code: [u8; 4],
+    pub(crate) licensee: Licensee,
+    pub(crate) old_licensee_code: u8,
+    pub(crate) new_licensee_code: [u8; 2],
+    pub(crate) cgb: bool,
+    pub(crate) cgb_flag: u8,
+    // The SGB flag at 0x146 is only honoured when the old licensee code is 0x33.
+    pub(crate) sgb: bool,
+    pub(crate) cartridge_type: CartridgeType,
+    pub(crate) cartridge_type_code: u8,
+    // Sizes are None when the header byte holding them (kept alongside) isn't a known value.
+    pub(crate) rom_size: Option<usize>,
+    pub(crate) num_rom_banks: Option<usize>,
+    pub(crate) rom_size_code: u8,
+    pub(crate) ram_size: Option<usize>,
+    pub(crate) ram_size_code: u8,
+    pub(crate) destination_code: DestinationCode,
+    pub(crate) version_number: u8,
+    pub(crate) header_checksum: u8,
+}
 
-        let ram_size = match rom[0x149] {
-            0x00 => 0,          // None
-            0x02 => 8 * 1024,   // 8KiB
-            0x03 => 32 * 1024,  // 32KiB
-            0x04 => 128 * 1024, // 128KiB
-            0x05 => 64 * 1024,  // 64KiB
-            _ => unimplemented!(),
+impl Header {
+    /*
+     * The title as text, ending at the first NUL. On CGB carts the last byte is the CGB flag instead.
+     */
+    pub(crate) fn title(&self) -> String {
+        let title = if self.cgb {
+            &self.title[..15]
+        } else {
+            &self.title[..]
         };
+        let end = title
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(title.len());
+        String::from_utf8_lossy(&title[..end]).into_owned()
+    }
+}
 
-        let destination_code = match rom[0x14A] {
-            0x00 => DestinationCode::Japanese,
-            0x01 => DestinationCode::Overseas,
-            _ => unimplemented!(),
-        };
+/*
+ * Parse the cartridge header, refusing ROMs whose header is truncated, fails its checksum or holds unknown values.
+ */
+pub(crate) fn parse(rom: &[u8]) -> Result<Header, HeaderError> {
+    let (header, errors) = parse_lenient(rom);
+    match errors.first() {
+        Some(error) => Err(*error),
+        None => Ok(header),
+    }
+}
+
+/*
+ * Parse the cartridge header, accepting whatever is there. Every problem `parse` would refuse is returned as a warning.
+ * Bytes missing from an undersized ROM read as 0xFF, like an empty bus.
+ */
+pub(crate) fn parse_lenient(rom: &[u8]) -> (Header, Vec<HeaderError>) {
+    let mut errors = Vec::new();
+    let mut bytes = [0xFF; HEADER_END];
+    let length = rom.len().min(HEADER_END);
+    bytes[..length].copy_from_slice(&rom[..length]);
+    if rom.len() < HEADER_END {
+        errors.push(HeaderError::TooShort { length: rom.len() });
+    }
+
+    let expected = checksum(&bytes);
+    if bytes[0x14D] != expected {
+        errors.push(HeaderError::HeaderChecksum {
+            expected,
+            actual: bytes[0x14D],
+        });
+    }
+
+    let mut title = [0u8; 16];
+    title.copy_from_slice(&bytes[0x134..0x144]);
+    let mut manufacturer_code = [0u8; 4];
+    manufacturer_code.copy_from_slice(&bytes[0x13F..0x143]);
+
+    let new_licensee_code = [bytes[0x144], bytes[0x145]];
+
+    let cartridge_type = CartridgeType::from_byte(bytes[0x147]).unwrap_or_else(|| {
+        errors.push(HeaderError::UnknownCartridgeType(bytes[0x147]));
+        CartridgeType::Unknown(bytes[0x147])
+    });
+
+    let num_rom_banks = rom_banks(bytes[0x148]);
+    if num_rom_banks.is_none() {
+        errors.push(HeaderError::UnknownRomSize(bytes[0x148]));
+    }
 
-        Self {
-            title,
-            manufacturer_code,
-            licensee: Licensee::None, // TODO
-            cgb: rom[0x143] == 0x80 || rom[0x143] == 0xC0,
-            sgb: rom[0x146] == 0x03,
-            cartridge_type,
-            rom_size,
-            num_rom_banks,
-            ram_size,
-            destination_code,
-            version_number: rom[0x14C],
+    let ram_size = ram_size(bytes[0x149]);
+    if ram_size.is_none() {
+        errors.push(HeaderError::UnknownRamSize(bytes[0x149]));
+    }
+
+    let destination_code = match bytes[0x14A] {
+        0x00 => DestinationCode::Japanese,
+        0x01 => DestinationCode::Overseas,
+        byte => {
+            errors.push(HeaderError::UnknownDestination(byte));
+            DestinationCode::Unknown(byte)
         }
+    };
+
+    let header = Header {
+        title,
+        manufacturer_code,
+        licensee: Licensee::new(bytes[0x14B], new_licensee_code),
+        old_licensee_code: bytes[0x14B],
+        new_licensee_code,
+        cgb: is_cgb(bytes[0x143]),
+        cgb_flag: bytes[0x143],
+        sgb: bytes[0x146] == 0x03 && bytes[0x14B] == USE_NEW_LICENSEE,
+        cartridge_type,
+        cartridge_type_code: bytes[0x147],
+        rom_size: num_rom_banks.map(|banks| banks * 16 * 1024),
+        num_rom_banks,
+        rom_size_code: bytes[0x148],
+        ram_size,
+        ram_size_code: bytes[0x149],
+        destination_code,
+        version_number: bytes[0x14C],
+        header_checksum: bytes[0x14D],
+    };
+    (header, errors)
+}
+
+/*
+ * Number of 16 KiB ROM banks from the header byte at 0x148.
+ */
+fn rom_banks(byte: u8) -> Option<usize> {
+    match byte {
+        0x00..=0x08 => Some(2 << byte), // 32KiB << byte
+        0x52 => Some(72),               // 1.1MiB
+        0x53 => Some(80),               // 1.2MiB
+        0x54 => Some(96),               // 1.5MiB
+        _ => None,
     }
 }
 
-fn checksum(rom: &[u8; 0x150]) -> u16 {
-    let mut sum: u16 = 0;
-    for byte in 0x134..0x14D {
-        sum = sum.wrapping_sub(rom[byte] as u16).wrapping_sub(1);
+/*
+ * Whether the header byte at 0x143 marks a CGB cartridge (enhanced or CGB only). Any other value is the last character
+ * of an old-style title.
+ */
+pub(crate) fn is_cgb(flag: u8) -> bool {
+    flag == 0x80 || flag == 0xC0
+}
+
+/*
+ * External RAM size in bytes from the header byte at 0x149.
+ */
+pub(crate) fn ram_size(byte: u8) -> Option<usize> {
+    match byte {
+        0x00 => Some(0),          // None
+        0x01 => Some(2 * 1024),   // 2KiB, unofficial but used by homebrew
+        0x02 => Some(8 * 1024),   // 8KiB
+        0x03 => Some(32 * 1024),  // 32KiB
+        0x04 => Some(128 * 1024), // 128KiB
+        0x05 => Some(64 * 1024),  // 64KiB
+        _ => None,
     }
-    sum
+}
+
+/*
+ * The header checksum stored at 0x14D, computed over 0x134-0x14C.
+ */
+pub(crate) fn checksum(rom: &[u8]) -> u8 {
+    rom[0x134..0x14D]
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_sub(*byte).wrapping_sub(1))
+}
+
+/*
+ * Verify the Nintendo logo and the global checksum at 0x14E-0x14F. Real hardware never checks the global checksum.
+ */
+pub(crate) fn diagnose(rom: &[u8]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    if let Some(offset) = logo_mismatch(rom, NINTENDO_LOGO.len()) {
+        diagnostics.push(Diagnostic::LogoMismatch { offset });
+    }
+    let expected = global_checksum(rom);
+    let actual = stored_global_checksum(rom);
+    if actual != expected {
+        diagnostics.push(Diagnostic::GlobalChecksum { expected, actual });
+    }
+    diagnostics
+}
+
+/*
+ * Whether the boot ROM would hand over to this cartridge rather than lock up: the logo (only its top half on CGB) and
+ * the header checksum must both match.
+ */
+pub(crate) fn boot_rom_accepts(rom: &[u8], cgb: bool) -> bool {
+    let checked = if cgb {
+        CGB_LOGO_CHECKED
+    } else {
+        NINTENDO_LOGO.len()
+    };
+    rom.len() >= HEADER_END && logo_mismatch(rom, checked).is_none() && rom[0x14D] == checksum(rom)
+}
+
+/*
+ * Offset of the first of the leading logo bytes that doesn't match, counting missing bytes as mismatches.
+ */
+fn logo_mismatch(rom: &[u8], length: usize) -> Option<usize> {
+    (0..length)
+        .find(|&index| rom.get(LOGO_START + index) != Some(&NINTENDO_LOGO[index]))
+        .map(|index| LOGO_START + index)
+}
+
+/*
+ * Sum of every byte in the ROM except the two holding the checksum itself.
+ */
+pub(crate) fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|(address, _)| !matches!(address, 0x14E | 0x14F))
+        .fold(0u16, |sum, (_, byte)| sum.wrapping_add(*byte as u16))
+}
+
+/*
+ * The global checksum as stored, big-endian, at 0x14E-0x14F.
+ */
+pub(crate) fn stored_global_checksum(rom: &[u8]) -> u16 {
+    let byte = |address: usize| rom.get(address).copied().unwrap_or(0xFF) as u16;
+    (byte(0x14E) << 8) | byte(0x14F)
 }
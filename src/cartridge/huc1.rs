@@ -0,0 +1,88 @@
+use super::*;
+
+/*
+ * HuC1: MBC1-like banking with an infrared LED and sensor. Writing 0x0E to 0x0000-0x1FFF switches 0xA000-0xBFFF from
+ * RAM to the IR port, where bit 0 drives the LED on write and reports received light on read.
+ */
+pub(super) struct Huc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    dirty: Dirty,
+
+    ir_mode: bool,
+    ir_led: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+}
+
+impl Huc1 {
+    pub(super) fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        Self {
+            rom,
+            ram: vec![0; ram_size],
+            dirty: Dirty::default(),
+            ir_mode: false,
+            ir_led: false,
+            rom_bank: 1,
+            ram_bank: 0,
+        }
+    }
+}
+
+impl Mbc for Huc1 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let offset = (address & 0x3FFF) as usize;
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, offset),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, offset),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ir_mode = byte & 0x0F == 0x0E,
+            0x2000..=0x3FFF => self.rom_bank = (byte & 0x3F).max(1),
+            0x4000..=0x5FFF => self.ram_bank = byte & 0b11,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.ir_mode {
+            // Nothing is ever on the other end, so no light is seen.
+            return 0xC0;
+        }
+        banked_read(
+            &self.ram,
+            RAM_BANK_SIZE,
+            self.ram_bank as usize,
+            (address & 0x1FFF) as usize,
+        )
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) {
+        if self.ir_mode {
+            self.ir_led = byte & 1 != 0;
+            return;
+        }
+        self.dirty.mark(banked_write(
+            &mut self.ram,
+            RAM_BANK_SIZE,
+            self.ram_bank as usize,
+            (address & 0x1FFF) as usize,
+            byte,
+        ));
+    }
+
+    fn save(&self) -> Option<Vec<u8>> {
+        battery_ram(true, &self.ram)
+    }
+
+    fn load(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.dirty.take()
+    }
+}
@@ -0,0 +1,271 @@
+use super::rtc::now;
+use super::*;
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+// Where the clock and alarm sit in the controller's nibble-addressed memory.
+const TIME_ADDRESS: usize = 0x00;
+const ALARM_ADDRESS: usize = 0x58;
+const ALARM_ENABLE_ADDRESS: usize = 0x5F;
+
+// The alarm counts in minutes, so checking it once per emulated second is plenty.
+const ALARM_CHECK_CYCLES: u32 = 4_194_304;
+
+// Last update time as 64-bit seconds, then the minutes and days of the clock and alarm as 16-bit words, then the alarm
+// enable, all little-endian.
+const FOOTER_SIZE: usize = 17;
+
+/*
+ * HuC3: up to 2 MiB of ROM, 32 KiB of RAM, and a clock with an alarm buzzer driven through a command register.
+ * The mode written to 0x0000-0x1FFF chooses what 0xA000-0xBFFF does:
+ *  0x0/0xA: RAM, writable only in 0xA
+ *  0xB: write a command, with the opcode in the upper nibble and its argument in the lower
+ *  0xC: read the response to the last command
+ *  0xD: semaphore, which reads 1 once a command has completed
+ *  0xE: infrared port
+ */
+pub(super) struct Huc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    dirty: Dirty,
+
+    mode: u8,
+    rom_bank: u8,
+    ram_bank: u8,
+
+    // 256 nibbles reached by commands through an address pointer.
+    memory: [u8; 256],
+    pointer: u8,
+    command: u8,
+    response: u8,
+
+    minutes: u16,
+    days: u16,
+    // Host time in milliseconds that the clock is up to date with.
+    updated_at: u64,
+    // Sounding until the next command, either because the game asked or because the alarm went off.
+    tone: bool,
+    alarm_check_cycles: u32,
+}
+
+impl Huc3 {
+    pub(super) fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        Self {
+            rom,
+            ram: vec![0; ram_size],
+            dirty: Dirty::default(),
+            mode: 0,
+            rom_bank: 1,
+            ram_bank: 0,
+            memory: [0; 256],
+            pointer: 0,
+            command: 0,
+            response: 0,
+            minutes: 0,
+            days: 0,
+            updated_at: now(),
+            tone: false,
+            alarm_check_cycles: 0,
+        }
+    }
+
+    /*
+     * The clock brought up to the current host time, and the time it is now up to date with.
+     */
+    fn caught_up(&self) -> (u16, u16, u64) {
+        let minutes = now().saturating_sub(self.updated_at) / 60_000;
+        let total = self.minutes as u64 + minutes;
+        let days = (self.days as u64 + total / MINUTES_PER_DAY as u64) & 0xFFF;
+        (
+            (total % MINUTES_PER_DAY as u64) as u16,
+            days as u16,
+            self.updated_at + minutes * 60_000,
+        )
+    }
+
+    fn update(&mut self) {
+        (self.minutes, self.days, self.updated_at) = self.caught_up();
+    }
+
+    fn read_nibbles(&self, address: usize, count: usize) -> u16 {
+        (0..count).fold(0, |value, index| {
+            value | (self.memory[address + index] as u16) << (index * 4)
+        })
+    }
+
+    fn write_nibbles(&mut self, address: usize, count: usize, value: u16) {
+        for index in 0..count {
+            self.memory[address + index] = ((value >> (index * 4)) & 0x0F) as u8;
+        }
+    }
+
+    fn run_command(&mut self, byte: u8) {
+        self.command = byte >> 4;
+        let argument = byte & 0x0F;
+        match self.command {
+            // Read the nibble at the pointer, then advance it.
+            0x1 => {
+                self.response = self.memory[self.pointer as usize];
+                self.pointer = self.pointer.wrapping_add(1);
+            }
+            // Write the nibble at the pointer, advancing it for 0x3 only.
+            0x2 | 0x3 => {
+                self.dirty
+                    .mark(replace(&mut self.memory[self.pointer as usize], argument));
+                if self.command == 0x3 {
+                    self.pointer = self.pointer.wrapping_add(1);
+                }
+            }
+            0x4 => self.pointer = (self.pointer & 0xF0) | argument,
+            0x5 => self.pointer = (self.pointer & 0x0F) | (argument << 4),
+            0x6 => self.run_extended_command(argument),
+            _ => {}
+        }
+    }
+
+    fn run_extended_command(&mut self, argument: u8) {
+        match argument {
+            // Copy the clock into memory.
+            0x0 => {
+                self.update();
+                self.write_nibbles(TIME_ADDRESS, 3, self.minutes);
+                self.write_nibbles(TIME_ADDRESS + 3, 3, self.days);
+            }
+            // Set the clock from memory.
+            0x1 => {
+                self.minutes = self.read_nibbles(TIME_ADDRESS, 3) % MINUTES_PER_DAY;
+                self.days = self.read_nibbles(TIME_ADDRESS + 3, 3);
+                self.updated_at = now();
+                self.dirty.mark(true);
+            }
+            // Status: report the clock as running.
+            0x2 => self.response = 1,
+            // Sound the buzzer.
+            0xE => self.tone = true,
+            _ => {}
+        }
+    }
+
+    fn alarm_due(&self) -> bool {
+        if self.memory[ALARM_ENABLE_ADDRESS] & 1 == 0 {
+            return false;
+        }
+        let (minutes, days, _) = self.caught_up();
+        let alarm_minutes = self.read_nibbles(ALARM_ADDRESS, 3);
+        let alarm_days = self.read_nibbles(ALARM_ADDRESS + 3, 3);
+        (days, minutes) >= (alarm_days, alarm_minutes)
+    }
+}
+
+impl Mbc for Huc3 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let offset = (address & 0x3FFF) as usize;
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, offset),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, offset),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => self.mode = byte & 0x0F,
+            0x2000..=0x3FFF => self.rom_bank = (byte & 0x7F).max(1),
+            0x4000..=0x5FFF => self.ram_bank = byte & 0b11,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        match self.mode {
+            0x0 | 0xA => banked_read(
+                &self.ram,
+                RAM_BANK_SIZE,
+                self.ram_bank as usize,
+                (address & 0x1FFF) as usize,
+            ),
+            // Bit 7 is always set, the last command sits in bits 4-6 and its result in the low nibble.
+            0xC => 0x80 | ((self.command & 0x07) << 4) | (self.response & 0x0F),
+            0xD => 0x01,
+            // No infrared light is ever received.
+            0xE => 0xC0,
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) {
+        match self.mode {
+            0xA => self.dirty.mark(banked_write(
+                &mut self.ram,
+                RAM_BANK_SIZE,
+                self.ram_bank as usize,
+                (address & 0x1FFF) as usize,
+                byte,
+            )),
+            0xB => {
+                // Any new command silences the buzzer.
+                self.tone = false;
+                self.run_command(byte);
+            }
+            _ => {}
+        }
+    }
+
+    /*
+     * RAM, followed by the clock and alarm footer. The clock is saved as last brought up to date rather than caught up
+     * to now; loading catches up.
+     */
+    fn save(&self) -> Option<Vec<u8>> {
+        let mut data = self.ram.clone();
+        data.extend((self.updated_at / 1000).to_le_bytes());
+        data.extend(self.minutes.to_le_bytes());
+        data.extend(self.days.to_le_bytes());
+        data.extend(self.read_nibbles(ALARM_ADDRESS, 3).to_le_bytes());
+        data.extend(self.read_nibbles(ALARM_ADDRESS + 3, 3).to_le_bytes());
+        data.push(self.memory[ALARM_ENABLE_ADDRESS] & 1);
+        Some(data)
+    }
+
+    fn load(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+        let Some(footer) = data.get(self.ram.len()..self.ram.len() + FOOTER_SIZE) else {
+            return;
+        };
+        let word = |offset: usize| u16::from_le_bytes([footer[offset], footer[offset + 1]]);
+        self.updated_at = u64::from_le_bytes(footer[0..8].try_into().unwrap()) * 1000;
+        self.minutes = word(8) % MINUTES_PER_DAY;
+        self.days = word(10) & 0xFFF;
+        self.write_nibbles(ALARM_ADDRESS, 3, word(12));
+        self.write_nibbles(ALARM_ADDRESS + 3, 3, word(14));
+        self.memory[ALARM_ENABLE_ADDRESS] = footer[16] & 1;
+        self.update();
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.dirty.take()
+    }
+
+    fn save_timestamp(&self) -> Option<Range<usize>> {
+        Some(self.ram.len()..self.ram.len() + 8)
+    }
+
+    /*
+     * Ring once when the alarm time is reached: the alarm disarms itself, and the tone lasts until the game's next
+     * command acknowledges it.
+     */
+    fn tick(&mut self, t_cycles: u8) {
+        self.alarm_check_cycles += t_cycles as u32;
+        if self.alarm_check_cycles < ALARM_CHECK_CYCLES {
+            return;
+        }
+        self.alarm_check_cycles = 0;
+        if self.alarm_due() {
+            self.memory[ALARM_ENABLE_ADDRESS] &= !1;
+            self.dirty.mark(true);
+            self.tone = true;
+        }
+    }
+
+    fn buzzing(&self) -> bool {
+        self.tone
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs::File;
+use std::path::Path;
+
+pub(crate) const IMAGE_WIDTH: usize = 128;
+pub(crate) const IMAGE_HEIGHT: usize = 112;
+
+/*
+ * Something the Pocket Camera's sensor can look at. Each capture returns a 128x112 greyscale frame, row by row, where 0
+ * is black and 255 is white.
+ */
+pub(crate) trait ImageSource {
+    fn capture(&mut self) -> Vec<u8>;
+}
+
+/*
+ * A still image loaded from a PNG file, centre-cropped to the sensor's aspect ratio and scaled down to fit.
+ */
+pub(crate) struct PngImage {
+    pixels: Vec<u8>,
+}
+
+impl PngImage {
+    pub(crate) fn open(path: &Path) -> Result<Self, png::DecodingError> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+
+        let channels = info.color_type.samples();
+        let (width, height) = (info.width as usize, info.height as usize);
+        let luma = |x: usize, y: usize| -> u8 {
+            let pixel = &buffer[y * info.line_size + x * channels..];
+            match info.color_type {
+                png::ColorType::Grayscale | png::ColorType::GrayscaleAlpha => pixel[0],
+                _ => {
+                    ((pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000)
+                        as u8
+                }
+            }
+        };
+
+        // Largest region with the sensor's aspect ratio, centred in the image.
+        let (crop_width, crop_height) = if width * IMAGE_HEIGHT > height * IMAGE_WIDTH {
+            (height * IMAGE_WIDTH / IMAGE_HEIGHT, height)
+        } else {
+            (width, width * IMAGE_HEIGHT / IMAGE_WIDTH)
+        };
+        let (left, top) = ((width - crop_width) / 2, (height - crop_height) / 2);
+
+        let mut pixels = Vec::with_capacity(IMAGE_WIDTH * IMAGE_HEIGHT);
+        for y in 0..IMAGE_HEIGHT {
+            for x in 0..IMAGE_WIDTH {
+                pixels.push(luma(
+                    left + x * crop_width / IMAGE_WIDTH,
+                    top + y * crop_height / IMAGE_HEIGHT,
+                ));
+            }
+        }
+        Ok(Self { pixels })
+    }
+}
+
+impl ImageSource for PngImage {
+    fn capture(&mut self) -> Vec<u8> {
+        self.pixels.clone()
+    }
+}
+
+/*
+ * A synthetic scene for when no image is given: a diagonal gradient behind a checkerboard that scrolls a little with
+ * every capture, so successive photos visibly differ.
+ */
+pub(crate) struct TestPattern {
+    frame: usize,
+}
+
+impl TestPattern {
+    pub(crate) fn new() -> Self {
+        Self { frame: 0 }
+    }
+}
+
+impl ImageSource for TestPattern {
+    fn capture(&mut self) -> Vec<u8> {
+        self.frame += 1;
+        let mut pixels = Vec::with_capacity(IMAGE_WIDTH * IMAGE_HEIGHT);
+        for y in 0..IMAGE_HEIGHT {
+            for x in 0..IMAGE_WIDTH {
+                let gradient = (x + y) * 255 / (IMAGE_WIDTH + IMAGE_HEIGHT);
+                let checker = ((x + self.frame) / 16 + y / 16).is_multiple_of(2);
+                pixels.push(if checker {
+                    gradient / 2
+                } else {
+                    128 + gradient / 2
+                } as u8);
+            }
+        }
+        pixels
+    }
+}
@@ -0,0 +1,147 @@
+use super::*;
+
+// Where the Nintendo logo sits in each game's header; MBC1M multicarts repeat it at the start of every 256 KiB game.
+const LOGO: std::ops::Range<usize> = 0x104..0x134;
+const MULTICART_GAME_SIZE: usize = 0x40000;
+
+/*
+ * MBC1: up to 2 MiB of ROM and 32 KiB of RAM.
+ * BANK1 (0x2000-0x3FFF) holds the low 5 bits of the ROM bank and BANK2 (0x4000-0x5FFF) two more bits, used either as
+ * bits 5-6 of the ROM bank or as the RAM bank. In mode 1 (0x6000-0x7FFF), BANK2 also applies to 0x0000-0x3FFF and RAM,
+ * which is how large-ROM carts reach banks 0x20/0x40/0x60 and 32 KiB RAM carts switch RAM banks.
+ */
+pub(super) struct Mbc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    dirty: Dirty,
+    battery: bool,
+    // MBC1M multicarts wire BANK2 to ROM address bits 4-5 instead of 5-6, skipping bit 4 of BANK1.
+    multicart: bool,
+
+    ram_enabled: bool,
+    bank_1: u8,
+    bank_2: u8,
+    mode: bool,
+}
+
+impl Mbc1 {
+    pub(super) fn new(rom: Vec<u8>, ram_size: usize, battery: bool) -> Self {
+        let multicart = is_multicart(&rom);
+        Self {
+            rom,
+            ram: vec![0; ram_size],
+            dirty: Dirty::default(),
+            battery,
+            multicart,
+            ram_enabled: false,
+            bank_1: 1,
+            bank_2: 0,
+            mode: false,
+        }
+    }
+
+    fn bank_2_shift(&self) -> u8 {
+        if self.multicart {
+            4
+        } else {
+            5
+        }
+    }
+
+    fn low_rom_bank(&self) -> usize {
+        if self.mode {
+            (self.bank_2 << self.bank_2_shift()) as usize
+        } else {
+            0
+        }
+    }
+
+    fn high_rom_bank(&self) -> usize {
+        let bank_1 = if self.multicart {
+            self.bank_1 & 0x0F
+        } else {
+            self.bank_1
+        };
+        ((self.bank_2 << self.bank_2_shift()) | bank_1) as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        if self.mode {
+            self.bank_2 as usize
+        } else {
+            0
+        }
+    }
+}
+
+impl Mbc for Mbc1 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let offset = (address & 0x3FFF) as usize;
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, self.low_rom_bank(), offset),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.high_rom_bank(), offset),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                // Bank 0 can't be selected here; the check covers all 5 bits, even on multicarts.
+                self.bank_1 = (byte & 0x1F).max(1);
+            }
+            0x4000..=0x5FFF => self.bank_2 = byte & 0b11,
+            _ => self.mode = byte & 1 != 0,
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        banked_read(
+            &self.ram,
+            RAM_BANK_SIZE,
+            self.ram_bank(),
+            (address & 0x1FFF) as usize,
+        )
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        let bank = self.ram_bank();
+        self.dirty.mark(banked_write(
+            &mut self.ram,
+            RAM_BANK_SIZE,
+            bank,
+            (address & 0x1FFF) as usize,
+            byte,
+        ));
+    }
+
+    fn save(&self) -> Option<Vec<u8>> {
+        battery_ram(self.battery, &self.ram)
+    }
+
+    fn load(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.dirty.take()
+    }
+}
+
+/*
+ * MBC1M carts are 1 MiB and hold a game (each with its own header) every 256 KiB. There is no header flag for them, so
+ * look for the logo of a second game.
+ */
+fn is_multicart(rom: &[u8]) -> bool {
+    if rom.len() != 4 * MULTICART_GAME_SIZE {
+        return false;
+    }
+    let second_game = MULTICART_GAME_SIZE + LOGO.start..MULTICART_GAME_SIZE + LOGO.end;
+    rom[LOGO] == rom[second_game]
+}
@@ -0,0 +1,82 @@
+use super::*;
+
+const RAM_SIZE: usize = 512;
+
+/*
+ * MBC2: up to 16 ROM banks and 512 half-bytes of RAM built into the controller.
+ * Both registers live in 0x0000-0x3FFF and are told apart by address bit 8: clear for RAM enable, set for the ROM bank.
+ */
+pub(super) struct Mbc2 {
+    rom: Vec<u8>,
+    ram: [u8; RAM_SIZE],
+    dirty: Dirty,
+    battery: bool,
+
+    ram_enabled: bool,
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    pub(super) fn new(rom: Vec<u8>, battery: bool) -> Self {
+        Self {
+            rom,
+            ram: [0; RAM_SIZE],
+            dirty: Dirty::default(),
+            battery,
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+}
+
+impl Mbc for Mbc2 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let offset = (address & 0x3FFF) as usize;
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, offset),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, offset),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x3FFF if address & 0x100 == 0 => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x0000..=0x3FFF => self.rom_bank = (byte & 0x0F).max(1),
+            _ => {}
+        }
+    }
+
+    /*
+     * Only the low nibble of each byte exists; the upper nibble reads as 1s. The 512 bytes repeat through 0xA000-0xBFFF.
+     */
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        0xF0 | self.ram[address as usize % RAM_SIZE]
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) {
+        if self.ram_enabled {
+            self.dirty.mark(replace(
+                &mut self.ram[address as usize % RAM_SIZE],
+                byte & 0x0F,
+            ));
+        }
+    }
+
+    fn save(&self) -> Option<Vec<u8>> {
+        self.battery
+            .then(|| self.ram.iter().map(|nibble| 0xF0 | nibble).collect())
+    }
+
+    fn load(&mut self, data: &[u8]) {
+        for (nibble, byte) in self.ram.iter_mut().zip(data) {
+            *nibble = byte & 0x0F;
+        }
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.dirty.take()
+    }
+}
@@ -0,0 +1,123 @@
+use super::rtc::{Rtc, FOOTER_SIZE, TIMESTAMP};
+use super::*;
+
+/*
+ * MBC3: up to 2 MiB of ROM, 32 KiB of RAM and an optional real-time clock.
+ * The RAM bank register (0x4000-0x5FFF) selects RAM banks 0x00-0x07 or, on carts with a clock, RTC registers 0x08-0x0C
+ * in their place at 0xA000-0xBFFF.
+ */
+pub(super) struct Mbc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    dirty: Dirty,
+    rtc: Option<Rtc>,
+    battery: bool,
+
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+}
+
+impl Mbc3 {
+    pub(super) fn new(rom: Vec<u8>, ram_size: usize, rtc: bool, battery: bool) -> Self {
+        Self {
+            rom,
+            ram: vec![0; ram_size],
+            dirty: Dirty::default(),
+            rtc: rtc.then(Rtc::new),
+            battery,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+        }
+    }
+}
+
+impl Mbc for Mbc3 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let offset = (address & 0x3FFF) as usize;
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, offset),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, offset),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = (byte & 0x7F).max(1),
+            0x4000..=0x5FFF => self.ram_bank = byte & 0x0F,
+            _ => {
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_latch(byte);
+                }
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        match (self.ram_bank, &self.rtc) {
+            (0x00..=0x07, _) => banked_read(
+                &self.ram,
+                RAM_BANK_SIZE,
+                self.ram_bank as usize,
+                (address & 0x1FFF) as usize,
+            ),
+            (0x08..=0x0C, Some(rtc)) => rtc.read(self.ram_bank),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        match (self.ram_bank, &mut self.rtc) {
+            (0x00..=0x07, _) => self.dirty.mark(banked_write(
+                &mut self.ram,
+                RAM_BANK_SIZE,
+                self.ram_bank as usize,
+                (address & 0x1FFF) as usize,
+                byte,
+            )),
+            (0x08..=0x0C, Some(rtc)) => {
+                rtc.write(self.ram_bank, byte);
+                self.dirty.mark(true);
+            }
+            _ => {}
+        }
+    }
+
+    /*
+     * RAM, followed by the clock footer on carts with a clock.
+     */
+    fn save(&self) -> Option<Vec<u8>> {
+        if !self.battery {
+            return None;
+        }
+        let mut data = self.ram.clone();
+        if let Some(rtc) = &self.rtc {
+            data.extend(rtc.save());
+        }
+        Some(data)
+    }
+
+    fn load(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+        if let (Some(rtc), Some(footer)) = (&mut self.rtc, data.get(self.ram.len()..)) {
+            rtc.load(&footer[..footer.len().min(FOOTER_SIZE)]);
+        }
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.dirty.take()
+    }
+
+    fn save_timestamp(&self) -> Option<Range<usize>> {
+        let end = self.ram.len();
+        (self.battery && self.rtc.is_some()).then(|| end + TIMESTAMP.start..end + TIMESTAMP.end)
+    }
+}
@@ -0,0 +1,99 @@
+use super::*;
+
+/*
+ * MBC5: up to 8 MiB of ROM in 512 banks and 128 KiB of RAM in 16 banks.
+ * Unlike earlier controllers, any bank including 0 can be mapped into 0x4000-0x7FFF. On carts with a rumble motor, bit 3
+ * of the RAM bank register drives the motor instead of selecting RAM.
+ */
+pub(super) struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    dirty: Dirty,
+    battery: bool,
+    has_rumble: bool,
+
+    ram_enabled: bool,
+    rom_bank: u16,
+    ram_bank: u8,
+    rumbling: bool,
+}
+
+impl Mbc5 {
+    pub(super) fn new(rom: Vec<u8>, ram_size: usize, rumble: bool, battery: bool) -> Self {
+        Self {
+            rom,
+            ram: vec![0; ram_size],
+            dirty: Dirty::default(),
+            battery,
+            has_rumble: rumble,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rumbling: false,
+        }
+    }
+}
+
+impl Mbc for Mbc5 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let offset = (address & 0x3FFF) as usize;
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, offset),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, offset),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = byte == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | byte as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | ((byte as u16 & 1) << 8),
+            0x4000..=0x5FFF if self.has_rumble => {
+                self.ram_bank = byte & 0x07;
+                self.rumbling = byte & 0x08 != 0;
+            }
+            0x4000..=0x5FFF => self.ram_bank = byte & 0x0F,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        banked_read(
+            &self.ram,
+            RAM_BANK_SIZE,
+            self.ram_bank as usize,
+            (address & 0x1FFF) as usize,
+        )
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) {
+        if self.ram_enabled {
+            self.dirty.mark(banked_write(
+                &mut self.ram,
+                RAM_BANK_SIZE,
+                self.ram_bank as usize,
+                (address & 0x1FFF) as usize,
+                byte,
+            ));
+        }
+    }
+
+    fn save(&self) -> Option<Vec<u8>> {
+        battery_ram(self.battery, &self.ram)
+    }
+
+    fn load(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.dirty.take()
+    }
+
+    fn rumbling(&self) -> bool {
+        self.rumbling
+    }
+}
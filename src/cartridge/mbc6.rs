@@ -0,0 +1,201 @@
+use super::*;
+
+const HALF_BANK_SIZE: usize = 0x2000;
+const RAM_HALF_BANK_SIZE: usize = 0x1000;
+const FLASH_SIZE: usize = 0x100000;
+// The flash is erased in 128 KiB sectors.
+const FLASH_SECTOR_SIZE: usize = 0x20000;
+
+// Manufacturer and device IDs of the Macronix MX29F008 flash, read back in ID mode.
+const FLASH_ID: [u8; 2] = [0xC2, 0x81];
+
+#[derive(Clone, Copy, PartialEq)]
+enum FlashState {
+    Read,
+    Unlock1,
+    Unlock2,
+    Program,
+    EraseUnlock,
+    EraseUnlock1,
+    EraseUnlock2,
+    Id,
+}
+
+/*
+ * MBC6, used by Net de Get: two independently banked 8 KiB windows at 0x4000-0x5FFF (A) and 0x6000-0x7FFF (B), each
+ * showing ROM or the 1 MiB flash, and two 4 KiB RAM windows at 0xA000-0xAFFF and 0xB000-0xBFFF.
+ *  0x0000-0x03FF: RAM enable        0x2000-0x27FF: window A bank   0x2800-0x2FFF: window A source (0x08 = flash)
+ *  0x0400-0x07FF: RAM bank A        0x3000-0x37FF: window B bank   0x3800-0x3FFF: window B source
+ *  0x0800-0x0BFF: RAM bank B
+ *  0x0C00-0x0FFF: flash enable
+ *  0x1000:        flash write enable
+ * The flash takes JEDEC-style commands, with the unlock addresses counted from the start of the flash.
+ */
+pub(super) struct Mbc6 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    dirty: Dirty,
+    flash: Vec<u8>,
+
+    ram_enabled: bool,
+    ram_banks: [u8; 2],
+    flash_enabled: bool,
+    flash_write_enabled: bool,
+    rom_banks: [u8; 2],
+    flash_selected: [bool; 2],
+    flash_state: FlashState,
+}
+
+impl Mbc6 {
+    pub(super) fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        Self {
+            rom,
+            ram: vec![0; ram_size],
+            dirty: Dirty::default(),
+            flash: vec![0xFF; FLASH_SIZE],
+            ram_enabled: false,
+            ram_banks: [0; 2],
+            flash_enabled: false,
+            flash_write_enabled: false,
+            rom_banks: [0; 2],
+            flash_selected: [false; 2],
+            flash_state: FlashState::Read,
+        }
+    }
+
+    fn flash_address(&self, window: usize, address: u16) -> usize {
+        (self.rom_banks[window] as usize * HALF_BANK_SIZE + (address as usize & 0x1FFF))
+            % FLASH_SIZE
+    }
+
+    fn write_flash(&mut self, address: usize, byte: u8) {
+        let command_address = address & 0x7FFF;
+        self.flash_state = match (self.flash_state, command_address, byte) {
+            (_, _, 0xF0) => FlashState::Read,
+            (FlashState::Read, 0x5555, 0xAA) => FlashState::Unlock1,
+            (FlashState::Unlock1, 0x2AAA, 0x55) => FlashState::Unlock2,
+            (FlashState::Unlock2, 0x5555, 0xA0) => FlashState::Program,
+            (FlashState::Unlock2, 0x5555, 0x80) => FlashState::EraseUnlock,
+            (FlashState::Unlock2, 0x5555, 0x90) => FlashState::Id,
+            (FlashState::Program, _, _) => {
+                if self.flash_write_enabled {
+                    // Programming can only clear bits.
+                    let programmed = self.flash[address] & byte;
+                    self.dirty
+                        .mark(replace(&mut self.flash[address], programmed));
+                }
+                FlashState::Read
+            }
+            (FlashState::EraseUnlock, 0x5555, 0xAA) => FlashState::EraseUnlock1,
+            (FlashState::EraseUnlock1, 0x2AAA, 0x55) => FlashState::EraseUnlock2,
+            (FlashState::EraseUnlock2, 0x5555, 0x10) => {
+                if self.flash_write_enabled {
+                    self.flash.fill(0xFF);
+                    self.dirty.mark(true);
+                }
+                FlashState::Read
+            }
+            (FlashState::EraseUnlock2, _, 0x30) => {
+                if self.flash_write_enabled {
+                    let start = address / FLASH_SECTOR_SIZE * FLASH_SECTOR_SIZE;
+                    self.flash[start..start + FLASH_SECTOR_SIZE].fill(0xFF);
+                    self.dirty.mark(true);
+                }
+                FlashState::Read
+            }
+            (FlashState::Id, _, _) => FlashState::Id,
+            _ => FlashState::Read,
+        };
+    }
+}
+
+impl Mbc for Mbc6 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let window = match address {
+            0x0000..=0x3FFF => return banked_read(&self.rom, ROM_BANK_SIZE, 0, address as usize),
+            0x4000..=0x5FFF => 0,
+            _ => 1,
+        };
+        if self.flash_selected[window] && self.flash_enabled {
+            let address = self.flash_address(window, address);
+            if self.flash_state == FlashState::Id {
+                return FLASH_ID.get(address & 0xFF).copied().unwrap_or(0x00);
+            }
+            return self.flash[address];
+        }
+        banked_read(
+            &self.rom,
+            HALF_BANK_SIZE,
+            self.rom_banks[window] as usize,
+            (address & 0x1FFF) as usize,
+        )
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x03FF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x0400..=0x07FF => self.ram_banks[0] = byte & 0x07,
+            0x0800..=0x0BFF => self.ram_banks[1] = byte & 0x07,
+            0x0C00..=0x0FFF => self.flash_enabled = byte & 1 != 0,
+            0x1000 => self.flash_write_enabled = byte & 1 != 0,
+            0x2000..=0x27FF => self.rom_banks[0] = byte & 0x7F,
+            0x2800..=0x2FFF => self.flash_selected[0] = byte == 0x08,
+            0x3000..=0x37FF => self.rom_banks[1] = byte & 0x7F,
+            0x3800..=0x3FFF => self.flash_selected[1] = byte == 0x08,
+            0x4000..=0x7FFF => {
+                let window = (address >= 0x6000) as usize;
+                if self.flash_selected[window] && self.flash_enabled {
+                    let address = self.flash_address(window, address);
+                    self.write_flash(address, byte);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        let window = (address >= 0xB000) as usize;
+        banked_read(
+            &self.ram,
+            RAM_HALF_BANK_SIZE,
+            self.ram_banks[window] as usize,
+            (address & 0x0FFF) as usize,
+        )
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) {
+        if self.ram_enabled {
+            let window = (address >= 0xB000) as usize;
+            self.dirty.mark(banked_write(
+                &mut self.ram,
+                RAM_HALF_BANK_SIZE,
+                self.ram_banks[window] as usize,
+                (address & 0x0FFF) as usize,
+                byte,
+            ));
+        }
+    }
+
+    /*
+     * RAM, followed by the whole flash.
+     */
+    fn save(&self) -> Option<Vec<u8>> {
+        let mut data = self.ram.clone();
+        data.extend(&self.flash);
+        Some(data)
+    }
+
+    fn load(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+        if let Some(flash) = data.get(self.ram.len()..) {
+            load_ram(&mut self.flash, flash);
+        }
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.dirty.take()
+    }
+}
@@ -0,0 +1,118 @@
+use super::eeprom::Eeprom;
+use super::*;
+
+// Accelerometer reading when level, and how far one g of tilt moves it.
+const ACCELEROMETER_CENTER: u16 = 0x81D0;
+const ACCELEROMETER_G: f32 = 0x70 as f32;
+// Value the latch is reset to by an erase.
+const ACCELEROMETER_ERASED: u16 = 0x8000;
+
+/*
+ * MBC7: up to 2 MiB of ROM, a 2-axis accelerometer and a 93LC56 EEPROM in place of RAM.
+ * 0xA000-0xAFFF holds 16 registers selected by address bits 4-7, reachable only once both RAM enables are set.
+ */
+pub(super) struct Mbc7 {
+    rom: Vec<u8>,
+    eeprom: Eeprom,
+
+    ram_enabled_1: bool,
+    ram_enabled_2: bool,
+    rom_bank: u8,
+
+    // Current tilt in g, positive to the right and towards the bottom of the screen.
+    tilt: (f32, f32),
+    latched: (u16, u16),
+    latch_erased: bool,
+}
+
+impl Mbc7 {
+    pub(super) fn new(rom: Vec<u8>) -> Self {
+        Self {
+            rom,
+            eeprom: Eeprom::new(),
+            ram_enabled_1: false,
+            ram_enabled_2: false,
+            rom_bank: 1,
+            tilt: (0.0, 0.0),
+            latched: (ACCELEROMETER_ERASED, ACCELEROMETER_ERASED),
+            latch_erased: false,
+        }
+    }
+
+    fn registers_enabled(&self) -> bool {
+        self.ram_enabled_1 && self.ram_enabled_2
+    }
+}
+
+fn accelerometer(g: f32) -> u16 {
+    (ACCELEROMETER_CENTER as f32 + g * ACCELEROMETER_G) as u16
+}
+
+impl Mbc for Mbc7 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let offset = (address & 0x3FFF) as usize;
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, offset),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, offset),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled_1 = byte == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = byte & 0x7F,
+            0x4000..=0x5FFF => self.ram_enabled_2 = byte == 0x40,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.registers_enabled() || address >= 0xB000 {
+            return 0xFF;
+        }
+        match (address >> 4) & 0x0F {
+            0x2 => self.latched.0 as u8,
+            0x3 => (self.latched.0 >> 8) as u8,
+            0x4 => self.latched.1 as u8,
+            0x5 => (self.latched.1 >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) {
+        if !self.registers_enabled() || address >= 0xB000 {
+            return;
+        }
+        match (address >> 4) & 0x0F {
+            // Writing 0x55 then 0xAA samples the accelerometer; a second latch needs another erase first.
+            0x0 if byte == 0x55 => {
+                self.latched = (ACCELEROMETER_ERASED, ACCELEROMETER_ERASED);
+                self.latch_erased = true;
+            }
+            0x1 if byte == 0xAA && self.latch_erased => {
+                self.latched = (accelerometer(self.tilt.0), accelerometer(self.tilt.1));
+                self.latch_erased = false;
+            }
+            0x8 => self.eeprom.write(byte),
+            _ => {}
+        }
+    }
+
+    fn save(&self) -> Option<Vec<u8>> {
+        Some(self.eeprom.save())
+    }
+
+    fn load(&mut self, data: &[u8]) {
+        self.eeprom.load(data);
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.eeprom.take_dirty()
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt = (x, y);
+    }
+}
@@ -0,0 +1,171 @@
+use super::*;
+
+/*
+ * MMM01: a multicart controller wrapping MBC1-style banking. It powers up unmapped, showing the last 32 KiB of ROM (the
+ * menu) at 0x0000-0x7FFF. The menu then writes the outer bank bits and masks for the chosen game and sets the map enable
+ * bit, after which the outer bits and masks are locked and only the MBC1-style registers respond.
+ * Masked bits of the low ROM and RAM bank registers keep the values they had when the game was mapped.
+ * The ROM/RAM multiplex option is not emulated.
+ */
+pub(super) struct Mmm01 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    dirty: Dirty,
+    battery: bool,
+
+    mapped: bool,
+    ram_enabled: bool,
+    // Bits 0-4 from 0x2000, 5-6 from 0x2000 and 7-8 from 0x4000 while unmapped.
+    rom_bank: u16,
+    // Bits 0-1 from 0x4000, 2-3 from 0x4000 while unmapped.
+    ram_bank: u8,
+    rom_bank_mask: u8,
+    ram_bank_mask: u8,
+    mode: bool,
+    mode_locked: bool,
+}
+
+impl Mmm01 {
+    pub(super) fn new(rom: Vec<u8>, ram_size: usize, battery: bool) -> Self {
+        Self {
+            rom,
+            ram: vec![0; ram_size],
+            dirty: Dirty::default(),
+            battery,
+            mapped: false,
+            ram_enabled: false,
+            rom_bank: 0,
+            ram_bank: 0,
+            rom_bank_mask: 0,
+            ram_bank_mask: 0,
+            mode: false,
+            mode_locked: false,
+        }
+    }
+
+    // Bits of the low ROM bank register the game can still change.
+    fn rom_writable_bits(&self) -> u16 {
+        0x1F & !((self.rom_bank_mask as u16) << 1)
+    }
+
+    fn ram_writable_bits(&self) -> u8 {
+        0b11 & !self.ram_bank_mask
+    }
+
+    fn rom_banks(&self) -> usize {
+        self.rom.len().div_ceil(ROM_BANK_SIZE).max(2)
+    }
+
+    fn low_rom_bank(&self) -> usize {
+        if !self.mapped {
+            return self.rom_banks() - 2;
+        }
+        (self.rom_bank & !self.rom_writable_bits()) as usize
+    }
+
+    fn high_rom_bank(&self) -> usize {
+        if !self.mapped {
+            return self.rom_banks() - 1;
+        }
+        let writable = self.rom_writable_bits();
+        if self.rom_bank & writable == 0 {
+            // Like MBC1, bank 0 of the game can't be selected here.
+            (self.rom_bank | (writable & 1)) as usize
+        } else {
+            self.rom_bank as usize
+        }
+    }
+
+    fn current_ram_bank(&self) -> usize {
+        if self.mode {
+            self.ram_bank as usize
+        } else {
+            (self.ram_bank & !self.ram_writable_bits()) as usize
+        }
+    }
+}
+
+impl Mbc for Mmm01 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let offset = (address & 0x3FFF) as usize;
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, self.low_rom_bank(), offset),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.high_rom_bank(), offset),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = byte & 0x0F == 0x0A;
+                if !self.mapped {
+                    self.ram_bank_mask = (byte >> 4) & 0b11;
+                    self.mapped = byte & 0x40 != 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                let writable = if self.mapped {
+                    self.rom_writable_bits()
+                } else {
+                    0x7F
+                };
+                self.rom_bank = (self.rom_bank & !writable) | (byte as u16 & writable);
+            }
+            0x4000..=0x5FFF => {
+                if self.mapped {
+                    let writable = self.ram_writable_bits();
+                    self.ram_bank = (self.ram_bank & !writable) | (byte & writable);
+                } else {
+                    self.ram_bank = byte & 0x0F;
+                    self.rom_bank = (self.rom_bank & 0x7F) | (((byte as u16 >> 4) & 0b11) << 7);
+                    self.mode_locked = byte & 0x40 != 0;
+                }
+            }
+            _ => {
+                if !self.mode_locked {
+                    self.mode = byte & 1 != 0;
+                }
+                if !self.mapped {
+                    self.rom_bank_mask = (byte >> 2) & 0x0F;
+                }
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        banked_read(
+            &self.ram,
+            RAM_BANK_SIZE,
+            self.current_ram_bank(),
+            (address & 0x1FFF) as usize,
+        )
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) {
+        if self.ram_enabled {
+            let bank = self.current_ram_bank();
+            self.dirty.mark(banked_write(
+                &mut self.ram,
+                RAM_BANK_SIZE,
+                bank,
+                (address & 0x1FFF) as usize,
+                byte,
+            ));
+        }
+    }
+
+    fn save(&self) -> Option<Vec<u8>> {
+        battery_ram(self.battery, &self.ram)
+    }
+
+    fn load(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.dirty.take()
+    }
+}
@@ -1 +1,232 @@
-mod header;
+mod camera;
+mod eeprom;
+pub(crate) mod header;
+mod huc1;
+mod huc3;
+mod image_source;
+mod licensee;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod mbc6;
+mod mbc7;
+mod mmm01;
+mod rom_only;
+mod rtc;
+mod tama5;
+
+use camera::Camera;
+use header::CartridgeType;
+use huc1::Huc1;
+use huc3::Huc3;
+use mbc1::Mbc1;
+use mbc2::Mbc2;
+use mbc3::Mbc3;
+use mbc5::Mbc5;
+use mbc6::Mbc6;
+use mbc7::Mbc7;
+use mmm01::Mmm01;
+use rom_only::RomOnly;
+use tama5::Tama5;
+
+use std::ops::Range;
+
+pub(crate) use image_source::{ImageSource, PngImage};
+pub(crate) use licensee::{Licensee, USE_NEW_LICENSEE};
+
+const ROM_BANK_SIZE: usize = 0x4000;
+const RAM_BANK_SIZE: usize = 0x2000;
+
+/*
+ * A memory bank controller, which sits between the bus and the cartridge's ROM and RAM.
+ * ROM accesses cover 0x0000-0x7FFF, where writes go to the controller's registers; RAM accesses cover 0xA000-0xBFFF.
+ * Addresses are passed through unchanged.
+ */
+pub(crate) trait Mbc {
+    fn read_rom(&self, address: u16) -> u8;
+    fn write_rom(&mut self, address: u16, byte: u8);
+    fn read_ram(&self, address: u16) -> u8;
+    fn write_ram(&mut self, address: u16, byte: u8);
+
+    /*
+     * Contents of battery-backed memory to persist, or None for carts without a battery.
+     */
+    fn save(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /*
+     * Restore battery-backed memory from a previous save.
+     */
+    fn load(&mut self, _data: &[u8]) {}
+
+    /*
+     * Whether battery-backed memory has changed since the last call: set by writes that change RAM, EEPROM or flash
+     * contents, or that set a clock. Bank switches and reads never set it.
+     */
+    fn take_dirty(&mut self) -> bool {
+        false
+    }
+
+    /*
+     * Where in the save a clock footer stores when it was written. These bytes change whenever the clock is brought up
+     * to date, so they are left out when deciding whether a save has changed.
+     */
+    fn save_timestamp(&self) -> Option<Range<usize>> {
+        None
+    }
+
+    /*
+     * Whether the cartridge's rumble motor is currently driven.
+     */
+    fn rumbling(&self) -> bool {
+        false
+    }
+
+    /*
+     * Whether the cartridge's buzzer is sounding.
+     */
+    fn buzzing(&self) -> bool {
+        false
+    }
+
+    /*
+     * Feed the cartridge's accelerometer, in g along each axis.
+     */
+    fn set_tilt(&mut self, _x: f32, _y: f32) {}
+
+    /*
+     * Advance anything on the cartridge that runs on the CPU clock.
+     */
+    fn tick(&mut self, _t_cycles: u8) {}
+
+    /*
+     * Choose what the cartridge's camera sees.
+     */
+    fn set_image_source(&mut self, _source: Box<dyn ImageSource>) {}
+}
+
+/*
+ * Build the controller named by the cartridge type byte in the header.
+ */
+pub(crate) fn new(rom: Vec<u8>) -> Box<dyn Mbc> {
+    // Header problems are for the frontend to report.
+    let (header, _) = header::parse_lenient(&rom);
+    let ram_size = header.ram_size.unwrap_or(0);
+
+    match header.cartridge_type {
+        CartridgeType::ROMOnly => Box::new(RomOnly::new(rom, 0, false)),
+        CartridgeType::ROMRAM => Box::new(RomOnly::new(rom, ram_size, false)),
+        CartridgeType::ROMRAMBattery => Box::new(RomOnly::new(rom, ram_size, true)),
+        CartridgeType::MBC1 => Box::new(Mbc1::new(rom, 0, false)),
+        CartridgeType::MBC1RAM => Box::new(Mbc1::new(rom, ram_size, false)),
+        CartridgeType::MBC1RAMBattery => Box::new(Mbc1::new(rom, ram_size, true)),
+        CartridgeType::MMM01 => Box::new(Mmm01::new(rom, 0, false)),
+        CartridgeType::MMM01RAM => Box::new(Mmm01::new(rom, ram_size, false)),
+        CartridgeType::MMM01RAMBattery => Box::new(Mmm01::new(rom, ram_size, true)),
+        CartridgeType::MBC2 => Box::new(Mbc2::new(rom, false)),
+        CartridgeType::MBC2Battery => Box::new(Mbc2::new(rom, true)),
+        CartridgeType::MBC3 => Box::new(Mbc3::new(rom, 0, false, false)),
+        CartridgeType::MBC3RAM => Box::new(Mbc3::new(rom, ram_size, false, false)),
+        CartridgeType::MBC3RAMBattery => Box::new(Mbc3::new(rom, ram_size, false, true)),
+        CartridgeType::MBC3TimerBattery => Box::new(Mbc3::new(rom, 0, true, true)),
+        CartridgeType::MBC3TimerRAMBattery => Box::new(Mbc3::new(rom, ram_size, true, true)),
+        CartridgeType::MBC5 => Box::new(Mbc5::new(rom, 0, false, false)),
+        CartridgeType::MBC5RAM => Box::new(Mbc5::new(rom, ram_size, false, false)),
+        CartridgeType::MBC5RAMBattery => Box::new(Mbc5::new(rom, ram_size, false, true)),
+        CartridgeType::MBC5Rumble => Box::new(Mbc5::new(rom, 0, true, false)),
+        CartridgeType::MBC5RumbleRAM => Box::new(Mbc5::new(rom, ram_size, true, false)),
+        CartridgeType::MBC5RumbleRAMBattery => Box::new(Mbc5::new(rom, ram_size, true, true)),
+        CartridgeType::MBC6 => Box::new(Mbc6::new(rom, ram_size)),
+        CartridgeType::MBC7SensorRumbleRAMBattery => Box::new(Mbc7::new(rom)),
+        CartridgeType::HuC1RAMBattery => Box::new(Huc1::new(rom, ram_size)),
+        CartridgeType::HuC3 => Box::new(Huc3::new(rom, ram_size)),
+        CartridgeType::BandaiTAMA5 => Box::new(Tama5::new(rom)),
+        CartridgeType::PocketCamera => Box::new(Camera::new(rom)),
+        // Already warned about by the header parser; plain ROM is the best guess.
+        CartridgeType::Unknown(_) => Box::new(RomOnly::new(rom, ram_size, false)),
+    }
+}
+
+/*
+ * How completely a cartridge type is emulated.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Support {
+    Full,
+    // Runs, with some of the hardware missing or approximated as described.
+    Partial(&'static str),
+    // No controller for it; the ROM is run as if it had none.
+    Unsupported,
+}
+
+pub(crate) fn support(cartridge_type: CartridgeType) -> Support {
+    match cartridge_type {
+        CartridgeType::MMM01 | CartridgeType::MMM01RAM | CartridgeType::MMM01RAMBattery => {
+            Support::Partial("no ROM/RAM multiplexing")
+        }
+        CartridgeType::HuC1RAMBattery | CartridgeType::HuC3 => {
+            Support::Partial("nothing on the infrared link")
+        }
+        CartridgeType::BandaiTAMA5 => Support::Partial("no TAMA6 clock"),
+        CartridgeType::PocketCamera => Support::Partial("approximate image sensor"),
+        CartridgeType::Unknown(_) => Support::Unsupported,
+        _ => Support::Full,
+    }
+}
+
+/*
+ * Read from a switchable bank, wrapping bank numbers beyond the end of the data like the unconnected address lines
+ * would. Missing data reads as open bus.
+ */
+fn banked_read(data: &[u8], bank_size: usize, bank: usize, offset: usize) -> u8 {
+    let banks = data.len().div_ceil(bank_size).max(1);
+    data.get((bank % banks) * bank_size + offset)
+        .copied()
+        .unwrap_or(0xFF)
+}
+
+/*
+ * Battery RAM for carts whose RAM is a plain array: only saved when there is a battery and some RAM to save.
+ */
+fn battery_ram(battery: bool, ram: &[u8]) -> Option<Vec<u8>> {
+    (battery && !ram.is_empty()).then(|| ram.to_vec())
+}
+
+fn load_ram(ram: &mut [u8], data: &[u8]) {
+    let length = ram.len().min(data.len());
+    ram[..length].copy_from_slice(&data[..length]);
+}
+
+/*
+ * Write to a switchable bank like `banked_read` reads from it. Returns whether the stored byte changed.
+ */
+fn banked_write(data: &mut [u8], bank_size: usize, bank: usize, offset: usize, byte: u8) -> bool {
+    let banks = data.len().div_ceil(bank_size).max(1);
+    data.get_mut((bank % banks) * bank_size + offset)
+        .is_some_and(|slot| replace(slot, byte))
+}
+
+/*
+ * The flag behind `Mbc::take_dirty`, kept by each cartridge with battery-backed memory.
+ */
+#[derive(Default)]
+struct Dirty(bool);
+
+impl Dirty {
+    fn mark(&mut self, changed: bool) {
+        self.0 |= changed;
+    }
+
+    fn take(&mut self) -> bool {
+        std::mem::take(&mut self.0)
+    }
+}
+
+/*
+ * Store a byte, returning whether it differed from what was there.
+ */
+fn replace(slot: &mut u8, byte: u8) -> bool {
+    std::mem::replace(slot, byte) != byte
+}
@@ -0,0 +1,56 @@
+use super::*;
+
+/*
+ * 32 KiB of ROM mapped directly, with up to 8 KiB of optional RAM.
+ */
+pub(super) struct RomOnly {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    dirty: Dirty,
+    battery: bool,
+}
+
+impl RomOnly {
+    pub(super) fn new(rom: Vec<u8>, ram_size: usize, battery: bool) -> Self {
+        Self {
+            rom,
+            ram: vec![0; ram_size.min(RAM_BANK_SIZE)],
+            dirty: Dirty::default(),
+            battery,
+        }
+    }
+}
+
+impl Mbc for RomOnly {
+    fn read_rom(&self, address: u16) -> u8 {
+        self.rom.get(address as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, _address: u16, _byte: u8) {}
+
+    fn read_ram(&self, address: u16) -> u8 {
+        banked_read(&self.ram, RAM_BANK_SIZE, 0, (address & 0x1FFF) as usize)
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) {
+        self.dirty.mark(banked_write(
+            &mut self.ram,
+            RAM_BANK_SIZE,
+            0,
+            (address & 0x1FFF) as usize,
+            byte,
+        ));
+    }
+
+    fn save(&self) -> Option<Vec<u8>> {
+        battery_ram(self.battery, &self.ram)
+    }
+
+    fn load(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.dirty.take()
+    }
+}
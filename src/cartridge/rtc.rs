@@ -0,0 +1,222 @@
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DH_DAY_HIGH: u8 = 0b0000_0001;
+const DH_HALT: u8 = 0b0100_0000;
+const DH_CARRY: u8 = 0b1000_0000;
+
+// Layout used by other emulators: the five registers then their latched copies as 32-bit words, then a 64-bit
+// timestamp. Some write a 32-bit timestamp instead, giving 44 bytes.
+pub(super) const FOOTER_SIZE: usize = 48;
+const SHORT_FOOTER_SIZE: usize = 44;
+pub(super) const TIMESTAMP: Range<usize> = 40..48;
+
+#[derive(Clone, Copy, Default)]
+struct Registers {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    day_low: u8,
+    day_high: u8,
+}
+
+impl Registers {
+    fn to_bytes(self) -> [u8; 5] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.day_low,
+            self.day_high,
+        ]
+    }
+
+    fn from_words(words: &[u8]) -> Self {
+        let word = |index: usize| words[index * 4];
+        Self {
+            seconds: word(0) & 0x3F,
+            minutes: word(1) & 0x3F,
+            hours: word(2) & 0x1F,
+            day_low: word(3),
+            day_high: word(4) & (DH_DAY_HIGH | DH_HALT | DH_CARRY),
+        }
+    }
+
+    fn day(&self) -> u16 {
+        ((self.day_high & DH_DAY_HIGH) as u16) << 8 | self.day_low as u16
+    }
+
+    fn set_day(&mut self, day: u16) {
+        self.day_low = day as u8;
+        self.day_high = (self.day_high & !DH_DAY_HIGH) | ((day >> 8) as u8 & DH_DAY_HIGH);
+    }
+
+    fn is_valid(&self) -> bool {
+        self.seconds < 60 && self.minutes < 60 && self.hours < 24
+    }
+
+    /*
+     * Advance by one second. Counters that were set out of range keep counting up to their bit width before wrapping
+     * to 0, and don't carry into the next counter when they do.
+     */
+    fn tick(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+        self.advance_days(1);
+    }
+
+    fn advance_days(&mut self, days: u64) {
+        let day = self.day() as u64 + days;
+        if day >= 512 {
+            self.day_high |= DH_CARRY;
+        }
+        self.set_day((day % 512) as u16);
+    }
+
+    fn advance(&mut self, mut seconds: u64) {
+        while seconds > 0 && !self.is_valid() {
+            self.tick();
+            seconds -= 1;
+        }
+        if seconds == 0 {
+            return;
+        }
+
+        let time_of_day =
+            self.hours as u64 * 3600 + self.minutes as u64 * 60 + self.seconds as u64 + seconds;
+        self.seconds = (time_of_day % 60) as u8;
+        self.minutes = (time_of_day / 60 % 60) as u8;
+        self.hours = (time_of_day / 3600 % 24) as u8;
+        self.advance_days(time_of_day / 86400);
+    }
+}
+
+/*
+ * The MBC3 real-time clock. It follows host wall time, so it keeps running while the emulator is closed as long as the
+ * save's timestamp is kept with it.
+ */
+pub(super) struct Rtc {
+    registers: Registers,
+    latched: Registers,
+    latch_armed: bool,
+    // Host time in milliseconds that the registers are up to date with.
+    updated_at: u64,
+}
+
+impl Rtc {
+    pub(super) fn new() -> Self {
+        Self {
+            registers: Registers::default(),
+            latched: Registers::default(),
+            latch_armed: false,
+            updated_at: now(),
+        }
+    }
+
+    /*
+     * The registers brought up to the current host time, and the time they are now up to date with.
+     */
+    fn caught_up(&self) -> (Registers, u64) {
+        let now = now();
+        if self.registers.day_high & DH_HALT != 0 {
+            return (self.registers, now);
+        }
+        let seconds = now.saturating_sub(self.updated_at) / 1000;
+        let mut registers = self.registers;
+        registers.advance(seconds);
+        (registers, self.updated_at + seconds * 1000)
+    }
+
+    fn update(&mut self) {
+        (self.registers, self.updated_at) = self.caught_up();
+    }
+
+    /*
+     * Writing 0 then 1 copies the running clock into the registers the game reads.
+     */
+    pub(super) fn write_latch(&mut self, byte: u8) {
+        if self.latch_armed && byte == 1 {
+            self.update();
+            self.latched = self.registers;
+        }
+        self.latch_armed = byte == 0;
+    }
+
+    /*
+     * Read a latched register, selected with 0x08-0x0C in the RAM bank register.
+     */
+    pub(super) fn read(&self, register: u8) -> u8 {
+        self.latched.to_bytes()[(register - 0x08) as usize]
+    }
+
+    pub(super) fn write(&mut self, register: u8, byte: u8) {
+        self.update();
+        let registers = &mut self.registers;
+        match register {
+            0x08 => {
+                registers.seconds = byte & 0x3F;
+                // Writing the seconds resets the sub-second divider.
+                self.updated_at = now();
+            }
+            0x09 => registers.minutes = byte & 0x3F,
+            0x0A => registers.hours = byte & 0x1F,
+            0x0B => registers.day_low = byte,
+            _ => registers.day_high = byte & (DH_DAY_HIGH | DH_HALT | DH_CARRY),
+        }
+    }
+
+    /*
+     * The registers as last brought up to date and when that was, rather than caught up to now, so the footer only
+     * changes when the game uses the clock. Loading catches up either way.
+     */
+    pub(super) fn save(&self) -> [u8; FOOTER_SIZE] {
+        let mut footer = [0; FOOTER_SIZE];
+        let registers = self.registers.to_bytes().into_iter();
+        let latched = self.latched.to_bytes().into_iter();
+        for (word, byte) in footer.chunks_exact_mut(4).zip(registers.chain(latched)) {
+            word[0] = byte;
+        }
+        footer[TIMESTAMP].copy_from_slice(&(self.updated_at / 1000).to_le_bytes());
+        footer
+    }
+
+    /*
+     * Restore the clock from a save footer, catching up on the time that passed since it was written.
+     */
+    pub(super) fn load(&mut self, footer: &[u8]) {
+        if footer.len() < SHORT_FOOTER_SIZE {
+            return;
+        }
+        self.registers = Registers::from_words(&footer[0..20]);
+        self.latched = Registers::from_words(&footer[20..40]);
+        let timestamp = if footer.len() >= FOOTER_SIZE {
+            u64::from_le_bytes(footer[40..48].try_into().unwrap())
+        } else {
+            u32::from_le_bytes(footer[40..44].try_into().unwrap()) as u64
+        };
+        self.updated_at = timestamp * 1000;
+        self.update();
+    }
+}
+
+/*
+ * Host wall time in milliseconds since the Unix epoch.
+ */
+pub(super) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis() as u64)
+}
@@ -0,0 +1,112 @@
+use super::*;
+
+const RAM_SIZE: usize = 32;
+
+// Registers selected by writing to 0xA001, then written 4 bits at a time through 0xA000.
+const BANK_LOW: u8 = 0x0;
+const BANK_HIGH: u8 = 0x1;
+const WRITE_LOW: u8 = 0x4;
+const WRITE_HIGH: u8 = 0x5;
+const ADDRESS_HIGH: u8 = 0x6;
+const ADDRESS_LOW: u8 = 0x7;
+const READY: u8 = 0xA;
+const READ_LOW: u8 = 0xC;
+const READ_HIGH: u8 = 0xD;
+
+/*
+ * Bandai TAMA5, used by Tamagotchi 3: ROM banking and 32 bytes of battery-backed RAM, all reached through a pair of
+ * registers at 0xA000 (data) and 0xA001 (register select). Writing the low address nibble carries out the command in
+ * the upper bits of ADDRESS_HIGH: 0 writes RAM, 1 reads it.
+ * The TAMA6 microcontroller's clock commands are accepted but ignored.
+ */
+pub(super) struct Tama5 {
+    rom: Vec<u8>,
+    ram: [u8; RAM_SIZE],
+    dirty: Dirty,
+
+    selected: u8,
+    registers: [u8; 16],
+    read_value: u8,
+}
+
+impl Tama5 {
+    pub(super) fn new(rom: Vec<u8>) -> Self {
+        Self {
+            rom,
+            ram: [0; RAM_SIZE],
+            dirty: Dirty::default(),
+            selected: 0,
+            registers: [0; 16],
+            read_value: 0,
+        }
+    }
+
+    fn rom_bank(&self) -> usize {
+        (self.registers[BANK_LOW as usize] | (self.registers[BANK_HIGH as usize] << 4)) as usize
+    }
+
+    fn address(&self) -> usize {
+        (((self.registers[ADDRESS_HIGH as usize] & 1) << 4) | self.registers[ADDRESS_LOW as usize])
+            as usize
+    }
+
+    fn run_command(&mut self) {
+        match self.registers[ADDRESS_HIGH as usize] >> 1 {
+            0x0 => {
+                let byte =
+                    (self.registers[WRITE_HIGH as usize] << 4) | self.registers[WRITE_LOW as usize];
+                self.dirty
+                    .mark(replace(&mut self.ram[self.address()], byte));
+            }
+            0x1 => self.read_value = self.ram[self.address()],
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for Tama5 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let offset = (address & 0x3FFF) as usize;
+        match address {
+            0x0000..=0x3FFF => banked_read(&self.rom, ROM_BANK_SIZE, 0, offset),
+            _ => banked_read(&self.rom, ROM_BANK_SIZE, self.rom_bank(), offset),
+        }
+    }
+
+    fn write_rom(&mut self, _address: u16, _byte: u8) {}
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if address & 1 != 0 {
+            return 0xFF;
+        }
+        match self.selected {
+            READY => 0xF1,
+            READ_LOW => 0xF0 | (self.read_value & 0x0F),
+            READ_HIGH => 0xF0 | (self.read_value >> 4),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) {
+        if address & 1 != 0 {
+            self.selected = byte & 0x0F;
+            return;
+        }
+        self.registers[self.selected as usize] = byte & 0x0F;
+        if self.selected == ADDRESS_LOW {
+            self.run_command();
+        }
+    }
+
+    fn save(&self) -> Option<Vec<u8>> {
+        Some(self.ram.to_vec())
+    }
+
+    fn load(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.dirty.take()
+    }
+}
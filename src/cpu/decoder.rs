@@ -0,0 +1,531 @@
+use std::fmt;
+
+use super::instructions::{Condition, Instruction, Vec, R16, R8, U3};
+use crate::memory::Memory;
+
+const CB_PREFIX: u8 = 0xCB;
+
+pub(crate) enum DecodeError {
+    IllegalOpcode { opcode: u8, address: u16 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::IllegalOpcode { opcode, address } => {
+                write!(f, "illegal opcode ${:02X} at ${:04X}", opcode, address)
+            }
+        }
+    }
+}
+
+/*
+ * Decode the instruction starting at address.
+ * Returns the instruction along with its length in bytes (including any 0xCB prefix and immediates).
+ */
+pub(crate) fn decode(memory: &Memory, address: u16) -> Result<(Instruction, u16), DecodeError> {
+    decode_from(address, |offset| memory.read(address.wrapping_add(offset)))
+}
+
+/*
+ * Decode the instruction starting at address as seen through the HALT bug: PC fails to increment after the opcode fetch,
+ * so the opcode byte is read twice and every operand is taken from one byte earlier.
+ * Returns the instruction along with the number of bytes PC actually advances by.
+ */
+pub(crate) fn decode_repeating_opcode(
+    memory: &Memory,
+    address: u16,
+) -> Result<(Instruction, u16), DecodeError> {
+    let (instruction, length) = decode_from(address, |offset| {
+        memory.read(address.wrapping_add(offset.saturating_sub(1)))
+    })?;
+    Ok((instruction, length - 1))
+}
+
+/*
+ * Decode an instruction whose bytes are supplied by fetch, given an offset from the opcode.
+ */
+fn decode_from(address: u16, fetch: impl Fn(u16) -> u8) -> Result<(Instruction, u16), DecodeError> {
+    let opcode = fetch(0);
+    let n8 = || fetch(1);
+    let e8 = || fetch(1) as i8;
+    let n16 = || {
+        let lower = fetch(1) as u16;
+        let upper = fetch(2) as u16;
+        (upper << 8) | lower
+    };
+
+    let decoded = match opcode {
+        0x00 => (Instruction::NOP, 1),
+        0x10 => (Instruction::STOP, 2),
+        0x76 => (Instruction::HALT, 1),
+        0xF3 => (Instruction::DI, 1),
+        0xFB => (Instruction::EI, 1),
+
+        // 16-bit loads & arithmetic
+        0x01 | 0x11 | 0x21 => (
+            Instruction::LD_R16_N16 {
+                r16: r16(opcode),
+                n16: n16(),
+            },
+            3,
+        ),
+        0x31 => (Instruction::LD_SP_N16 { n16: n16() }, 3),
+        0x08 => (Instruction::LD_N16_PNTR_SP { n16: n16() }, 3),
+        0x03 | 0x13 | 0x23 => (Instruction::INC_R16 { r16: r16(opcode) }, 1),
+        0x33 => (Instruction::INC_SP, 1),
+        0x0B | 0x1B | 0x2B => (Instruction::DEC_R16 { r16: r16(opcode) }, 1),
+        0x3B => (Instruction::DEC_SP, 1),
+        0x09 | 0x19 | 0x29 => (Instruction::ADD_HL_R16 { r16: r16(opcode) }, 1),
+        0x39 => (Instruction::ADD_HL_SP, 1),
+        0xE8 => (Instruction::ADD_SP_E8 { e8: e8() }, 2),
+        0xF8 => (Instruction::LD_HL_SPE8 { e8: e8() }, 2),
+        0xF9 => (Instruction::LD_SP_HL, 1),
+
+        // Indirect loads through A
+        0x02 => (Instruction::LD_R16_PNTR_A { r16: R16::BC }, 1),
+        0x12 => (Instruction::LD_R16_PNTR_A { r16: R16::DE }, 1),
+        0x22 => (Instruction::LD_HLI_PNTR_A, 1),
+        0x32 => (Instruction::LD_HLD_PNTR_A, 1),
+        0x0A => (Instruction::LD_A_R16_PNTR { r16: R16::BC }, 1),
+        0x1A => (Instruction::LD_A_R16_PNTR { r16: R16::DE }, 1),
+        0x2A => (Instruction::LD_A_HLI_PNTR, 1),
+        0x3A => (Instruction::LD_A_HLD_PNTR, 1),
+        0xEA => (Instruction::LD_N16_PNTR_A { n16: n16() }, 3),
+        0xFA => (Instruction::LD_A_N16_PNTR { n16: n16() }, 3),
+        0xE0 => (
+            Instruction::LDH_N16_PNTR_A {
+                n16: 0xFF00 | n8() as u16,
+            },
+            2,
+        ),
+        0xF0 => (
+            Instruction::LDH_A_N16_PNTR {
+                n16: 0xFF00 | n8() as u16,
+            },
+            2,
+        ),
+        0xE2 => (Instruction::LDH_C_PNTR_A, 1),
+        0xF2 => (Instruction::LDH_A_C_PNTR, 1),
+
+        // 8-bit increments, decrements & immediate loads
+        0x04 | 0x0C | 0x14 | 0x1C | 0x24 | 0x2C | 0x3C => (
+            Instruction::INC_R8 {
+                r8: r8(opcode >> 3),
+            },
+            1,
+        ),
+        0x34 => (Instruction::INC_HL_PNTR, 1),
+        0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x3D => (
+            Instruction::DEC_R8 {
+                r8: r8(opcode >> 3),
+            },
+            1,
+        ),
+        0x35 => (Instruction::DEC_HL_PNTR, 1),
+        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x3E => (
+            Instruction::LD_R8_N8 {
+                dest: r8(opcode >> 3),
+                n8: n8(),
+            },
+            2,
+        ),
+        0x36 => (Instruction::LD_HL_PNTR_N8 { n8: n8() }, 2),
+
+        // Accumulator rotates & flag operations
+        0x07 => (Instruction::RLCA, 1),
+        0x0F => (Instruction::RRCA, 1),
+        0x17 => (Instruction::RLA, 1),
+        0x1F => (Instruction::RRA, 1),
+        0x27 => (Instruction::DAA, 1),
+        0x2F => (Instruction::CPL, 1),
+        0x37 => (Instruction::SCF {}, 1),
+        0x3F => (Instruction::CCF, 1),
+
+        // Relative jumps
+        0x18 => (Instruction::JR_N16 { offset: e8() }, 2),
+        0x20 | 0x28 | 0x30 | 0x38 => (
+            Instruction::JR_CC_N16 {
+                condition: condition(opcode),
+                offset: e8(),
+            },
+            2,
+        ),
+
+        // 8-bit register loads (0x76 is HALT, handled above)
+        0x40..=0x7F => {
+            let instruction = match (opcode >> 3 & 0b111, opcode & 0b111) {
+                (0b110, src) => Instruction::LD_HL_PNTR_R8 { r8: r8(src) },
+                (dest, 0b110) => Instruction::LD_R8_HL_PNTR { r8: r8(dest) },
+                (dest, src) => Instruction::LD_R8_R8 {
+                    dest: r8(dest),
+                    src: r8(src),
+                },
+            };
+            (instruction, 1)
+        }
+
+        // 8-bit arithmetic & logic on A
+        0x80..=0xBF => (alu(opcode >> 3 & 0b111, opcode & 0b111), 1),
+        0xC6 => (Instruction::ADD_A_N8 { n8: n8() }, 2),
+        0xCE => (Instruction::ADC_A_N8 { n8: n8() }, 2),
+        0xD6 => (Instruction::SUB_A_N8 { n8: n8() }, 2),
+        0xDE => (Instruction::SBC_A_N8 { n8: n8() }, 2),
+        0xE6 => (Instruction::AND_A_N8 { n8: n8() }, 2),
+        0xEE => (Instruction::XOR_A_N8 { n8: n8() }, 2),
+        0xF6 => (Instruction::OR_A_N8 { n8: n8() }, 2),
+        0xFE => (Instruction::CP_A_N8 { n8: n8() }, 2),
+
+        // Stack
+        0xC1 | 0xD1 | 0xE1 => (
+            Instruction::POP_R16 {
+                r16: r16(opcode - 0xC0),
+            },
+            1,
+        ),
+        0xF1 => (Instruction::POP_AF, 1),
+        0xC5 | 0xD5 | 0xE5 => (
+            Instruction::PUSH_R16 {
+                r16: r16(opcode - 0xC0),
+            },
+            1,
+        ),
+        0xF5 => (Instruction::PUSH_AF, 1),
+
+        // Absolute jumps, calls & returns
+        0xC3 => (Instruction::JP_N16 { n16: n16() }, 3),
+        0xC2 | 0xCA | 0xD2 | 0xDA => (
+            Instruction::JP_CC_N16 {
+                condition: condition(opcode),
+                n16: n16(),
+            },
+            3,
+        ),
+        0xE9 => (Instruction::JP_HL, 1),
+        0xCD => (Instruction::CALL_N16 { n16: n16() }, 3),
+        0xC4 | 0xCC | 0xD4 | 0xDC => (
+            Instruction::CALL_CC_N16 {
+                condition: condition(opcode),
+                n16: n16(),
+            },
+            3,
+        ),
+        0xC9 => (Instruction::RET, 1),
+        0xD9 => (Instruction::RETI, 1),
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => (
+            Instruction::RET_CC {
+                condition: condition(opcode),
+            },
+            1,
+        ),
+        0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => (
+            Instruction::RST {
+                vec: vec(opcode >> 3),
+            },
+            1,
+        ),
+
+        CB_PREFIX => (decode_prefixed(n8()), 2),
+
+        0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD => {
+            return Err(DecodeError::IllegalOpcode { opcode, address });
+        }
+    };
+    Ok(decoded)
+}
+
+/*
+ * Decode the byte following a 0xCB prefix. Every value is a valid instruction.
+ */
+fn decode_prefixed(opcode: u8) -> Instruction {
+    let target = opcode & 0b111;
+    let hl = target == 0b110;
+    let u3 = u3(opcode >> 3);
+
+    match (opcode >> 3, hl) {
+        (0, false) => Instruction::RLC_R8 { r8: r8(target) },
+        (0, true) => Instruction::RLC_HL_PNTR,
+        (1, false) => Instruction::RRC_R8 { r8: r8(target) },
+        (1, true) => Instruction::RRC_HL_PNTR,
+        (2, false) => Instruction::RL_R8 { r8: r8(target) },
+        (2, true) => Instruction::RL_HL_PNTR,
+        (3, false) => Instruction::RR_R8 { r8: r8(target) },
+        (3, true) => Instruction::RR_HL_PNTR,
+        (4, false) => Instruction::SLA_R8 { r8: r8(target) },
+        (4, true) => Instruction::SLA_HL_PNTR,
+        (5, false) => Instruction::SRA_R8 { r8: r8(target) },
+        (5, true) => Instruction::SRA_HL_PNTR,
+        (6, false) => Instruction::SWAP_R8 { r8: r8(target) },
+        (6, true) => Instruction::SWAP_HL_PNTR,
+        (7, false) => Instruction::SRL_R8 { r8: r8(target) },
+        (7, true) => Instruction::SRL_HL_PNTR,
+        (0x08..=0x0F, false) => Instruction::BIT_U3_R8 { u3, r8: r8(target) },
+        (0x08..=0x0F, true) => Instruction::BIT_U3_HL_PNTR { u3 },
+        (0x10..=0x17, false) => Instruction::RES_U3_R8 { u3, r8: r8(target) },
+        (0x10..=0x17, true) => Instruction::RES_U3_HL_PNTR { u3 },
+        (_, false) => Instruction::SET_U3_R8 { u3, r8: r8(target) },
+        (_, true) => Instruction::SET_U3_HL_PNTR { u3 },
+    }
+}
+
+/*
+ * Decode one of the eight A-register operations in the 0x80-0xBF block.
+ */
+fn alu(operation: u8, target: u8) -> Instruction {
+    let hl = target == 0b110;
+    match (operation, hl) {
+        (0, false) => Instruction::ADD_A_R8 { r8: r8(target) },
+        (0, true) => Instruction::ADD_A_HL_PNTR,
+        (1, false) => Instruction::ADC_A_R8 { r8: r8(target) },
+        (1, true) => Instruction::ADC_A_HL_PNTR,
+        (2, false) => Instruction::SUB_A_R8 { r8: r8(target) },
+        (2, true) => Instruction::SUB_A_HL_PNTR,
+        (3, false) => Instruction::SBC_A_R8 { r8: r8(target) },
+        (3, true) => Instruction::SBC_A_HL_PNTR,
+        (4, false) => Instruction::AND_A_R8 { r8: r8(target) },
+        (4, true) => Instruction::AND_A_HL_PNTR,
+        (5, false) => Instruction::XOR_A_R8 { r8: r8(target) },
+        (5, true) => Instruction::XOR_A_HL_PNTR,
+        (6, false) => Instruction::OR_A_R8 { r8: r8(target) },
+        (6, true) => Instruction::OR_A_HL_PNTR,
+        (_, false) => Instruction::CP_A_R8 { r8: r8(target) },
+        (_, true) => Instruction::CP_A_HL_PNTR,
+    }
+}
+
+/*
+ * Register encoded in the lower three bits. 0b110 ([HL]) must be handled by the caller.
+ */
+fn r8(bits: u8) -> R8 {
+    match bits & 0b111 {
+        0 => R8::B,
+        1 => R8::C,
+        2 => R8::D,
+        3 => R8::E,
+        4 => R8::H,
+        5 => R8::L,
+        7 => R8::A,
+        _ => unreachable!("[HL] is not an 8-bit register"),
+    }
+}
+
+/*
+ * Register pair encoded in bits 4-5. 0b11 (SP or AF) must be handled by the caller.
+ */
+fn r16(opcode: u8) -> R16 {
+    match opcode >> 4 & 0b11 {
+        0 => R16::BC,
+        1 => R16::DE,
+        2 => R16::HL,
+        _ => unreachable!("SP and AF are not general purpose register pairs"),
+    }
+}
+
+fn condition(opcode: u8) -> Condition {
+    match opcode >> 3 & 0b11 {
+        0 => Condition::NZ,
+        1 => Condition::Z,
+        2 => Condition::NC,
+        _ => Condition::C,
+    }
+}
+
+fn u3(bits: u8) -> U3 {
+    match bits & 0b111 {
+        0 => U3::Zero,
+        1 => U3::One,
+        2 => U3::Two,
+        3 => U3::Three,
+        4 => U3::Four,
+        5 => U3::Five,
+        6 => U3::Six,
+        _ => U3::Seven,
+    }
+}
+
+fn vec(bits: u8) -> Vec {
+    match bits & 0b111 {
+        0 => Vec::X00,
+        1 => Vec::X08,
+        2 => Vec::X10,
+        3 => Vec::X18,
+        4 => Vec::X20,
+        5 => Vec::X28,
+        6 => Vec::X30,
+        _ => Vec::X38,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Whether a decoded instruction is the one a case expects.
+    type Expected = fn(&Instruction) -> bool;
+
+    const ILLEGAL_OPCODES: [u8; 11] = [
+        0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+    ];
+
+    /*
+     * Decode from a byte slice placed at 0x1234; reads past its end see 0x00.
+     */
+    fn decode_bytes(bytes: &[u8]) -> Result<(Instruction, u16), DecodeError> {
+        decode_from(0x1234, |offset| {
+            bytes.get(offset as usize).copied().unwrap_or(0x00)
+        })
+    }
+
+    #[test]
+    fn illegal_opcodes() {
+        for opcode in ILLEGAL_OPCODES {
+            assert!(
+                matches!(
+                    decode_bytes(&[opcode]),
+                    Err(DecodeError::IllegalOpcode { opcode: decoded, address: 0x1234 })
+                        if decoded == opcode
+                ),
+                "${:02X} should be illegal",
+                opcode
+            );
+        }
+    }
+
+    #[test]
+    fn every_other_opcode_decodes() {
+        for opcode in (0..=0xFF).filter(|opcode| !ILLEGAL_OPCODES.contains(opcode)) {
+            match decode_bytes(&[opcode]) {
+                Ok((_, length)) => assert!((1..=3).contains(&length), "${:02X}", opcode),
+                Err(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn immediates() {
+        let cases: [(&[u8], u16, Expected); 12] = [
+            (&[0x3E, 0x42], 2, |i| {
+                matches!(
+                    i,
+                    Instruction::LD_R8_N8 {
+                        dest: R8::A,
+                        n8: 0x42
+                    }
+                )
+            }),
+            (&[0x36, 0x99], 2, |i| {
+                matches!(i, Instruction::LD_HL_PNTR_N8 { n8: 0x99 })
+            }),
+            (&[0xFE, 0x10], 2, |i| {
+                matches!(i, Instruction::CP_A_N8 { n8: 0x10 })
+            }),
+            (&[0xE0, 0x40], 2, |i| {
+                matches!(i, Instruction::LDH_N16_PNTR_A { n16: 0xFF40 })
+            }),
+            (&[0x01, 0x34, 0x12], 3, |i| {
+                matches!(
+                    i,
+                    Instruction::LD_R16_N16 {
+                        r16: R16::BC,
+                        n16: 0x1234
+                    }
+                )
+            }),
+            (&[0x08, 0x00, 0xC0], 3, |i| {
+                matches!(i, Instruction::LD_N16_PNTR_SP { n16: 0xC000 })
+            }),
+            (&[0xC3, 0x50, 0x01], 3, |i| {
+                matches!(i, Instruction::JP_N16 { n16: 0x0150 })
+            }),
+            (&[0xDC, 0xFF, 0xFF], 3, |i| {
+                matches!(
+                    i,
+                    Instruction::CALL_CC_N16 {
+                        condition: Condition::C,
+                        n16: 0xFFFF
+                    }
+                )
+            }),
+            (&[0x18, 0xFE], 2, |i| {
+                matches!(i, Instruction::JR_N16 { offset: -2 })
+            }),
+            (&[0x20, 0x80], 2, |i| {
+                matches!(
+                    i,
+                    Instruction::JR_CC_N16 {
+                        condition: Condition::NZ,
+                        offset: -128
+                    }
+                )
+            }),
+            (&[0xE8, 0x7F], 2, |i| {
+                matches!(i, Instruction::ADD_SP_E8 { e8: 127 })
+            }),
+            (&[0xF8, 0xFF], 2, |i| {
+                matches!(i, Instruction::LD_HL_SPE8 { e8: -1 })
+            }),
+        ];
+        for (bytes, expected_length, expected) in cases {
+            match decode_bytes(bytes) {
+                Ok((instruction, length)) => {
+                    assert_eq!(length, expected_length, "{:02X?}", bytes);
+                    assert!(expected(&instruction), "{:02X?}", bytes);
+                }
+                Err(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn prefixed() {
+        let cases: [(u8, Expected); 10] = [
+            (0x00, |i| matches!(i, Instruction::RLC_R8 { r8: R8::B })),
+            (0x06, |i| matches!(i, Instruction::RLC_HL_PNTR)),
+            (0x1B, |i| matches!(i, Instruction::RR_R8 { r8: R8::E })),
+            (0x37, |i| matches!(i, Instruction::SWAP_R8 { r8: R8::A })),
+            (0x3E, |i| matches!(i, Instruction::SRL_HL_PNTR)),
+            (0x46, |i| {
+                matches!(i, Instruction::BIT_U3_HL_PNTR { u3: U3::Zero })
+            }),
+            (0x7C, |i| {
+                matches!(
+                    i,
+                    Instruction::BIT_U3_R8 {
+                        u3: U3::Seven,
+                        r8: R8::H
+                    }
+                )
+            }),
+            (0x95, |i| {
+                matches!(
+                    i,
+                    Instruction::RES_U3_R8 {
+                        u3: U3::Two,
+                        r8: R8::L
+                    }
+                )
+            }),
+            (0xDE, |i| {
+                matches!(i, Instruction::SET_U3_HL_PNTR { u3: U3::Three })
+            }),
+            (0xFF, |i| {
+                matches!(
+                    i,
+                    Instruction::SET_U3_R8 {
+                        u3: U3::Seven,
+                        r8: R8::A
+                    }
+                )
+            }),
+        ];
+        for (opcode, expected) in cases {
+            match decode_bytes(&[CB_PREFIX, opcode]) {
+                Ok((instruction, length)) => {
+                    assert_eq!(length, 2, "$CB ${:02X}", opcode);
+                    assert!(expected(&instruction), "$CB ${:02X}", opcode);
+                }
+                Err(e) => panic!("{}", e),
+            }
+        }
+    }
+}
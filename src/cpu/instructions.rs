@@ -132,7 +132,7 @@ pub(crate) enum Instruction {
     JR_CC_N16 { condition: Condition, offset: i8 },
     LD_R8_R8 { dest: R8, src: R8 },
     LD_R8_N8 { dest: R8, n8: u8 },
-    LD_R16_R16 { dest: R16, src: R16 },
+    LD_R16_N16 { r16: R16, n16: u16 },
     LD_HL_PNTR_R8 { r8: R8 },
     LD_HL_PNTR_N8 { n8: u8 },
     LD_R8_HL_PNTR { r8: R8 },
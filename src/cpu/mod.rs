@@ -1,40 +1,134 @@
+mod decoder;
 mod instructions;
 mod registers;
 
+use crate::io::Interrupt;
 use crate::memory::Memory;
 use instructions::{Instruction, R16, R8, U3};
-use registers::{Flags, Registers};
+use registers::Registers;
 
 #[derive(PartialEq)]
 enum ImeState {
-    UNSET,
-    PENDING_NEW_INSTRUCTION,
-    PENDING_INSTRUCTION_COMPLETION,
-    SET,
+    Unset,
+    PendingNewInstruction,
+    PendingInstructionCompletion,
+    Set,
 }
 pub(crate) struct Cpu {
     registers: Registers,
     ime_state: ImeState,
     low_power_mode: bool,
     very_low_power_mode: bool,
+    speed_switch_cycles: u16,
+    halt_bug: bool,
+    locked: bool,
 }
 
 impl Cpu {
     pub(crate) fn new() -> Self {
         Self {
             registers: Registers::new(),
-            ime_state: ImeState::UNSET,
+            ime_state: ImeState::Unset,
             low_power_mode: false,      // HALT
             very_low_power_mode: false, // STOP
+            speed_switch_cycles: 0,     // CGB speed switch pause
+            halt_bug: false,            // HALT with IME clear & an interrupt pending
+            locked: false,              // Illegal opcode or rejected cartridge
         }
     }
 
+    /*
+     * Stop executing for good, as the boot ROM does when it rejects a cartridge.
+     */
+    pub(crate) fn lock(&mut self) {
+        self.locked = true;
+    }
+
+    /*
+     * Fetch the instruction at PC, advance PC past it and execute it, then service the highest priority pending interrupt.
+     * Returns the number of T-cycles taken, including the interrupt dispatch.
+     */
+    pub(crate) fn step(&mut self, memory: &mut Memory) -> u8 {
+        if self.locked {
+            return 4;
+        }
+
+        if self.speed_switch_cycles > 0 {
+            self.speed_switch_cycles -= 4;
+            return 4;
+        }
+
+        if self.very_low_power_mode {
+            if !memory.joypad_line_low() {
+                return 4;
+            }
+            self.very_low_power_mode = false;
+        }
+
+        if self.low_power_mode {
+            if memory.pending_interrupts() == 0 {
+                return 4;
+            }
+            // Any enabled & requested interrupt wakes the CPU, even when IME is clear.
+            // Without IME, execution simply resumes after the HALT.
+            self.low_power_mode = false;
+            return 4 + self.service_interrupt(memory);
+        }
+
+        let decoded = if self.halt_bug {
+            self.halt_bug = false;
+            decoder::decode_repeating_opcode(memory, self.registers.pc)
+        } else {
+            decoder::decode(memory, self.registers.pc)
+        };
+
+        let t_cycles = match decoded {
+            Ok((instruction, length)) => {
+                self.registers.pc = self.registers.pc.wrapping_add(length);
+                let t_cycles = self.execute(instruction, memory);
+                self.update_ime();
+                t_cycles
+            }
+            Err(decoder::DecodeError::IllegalOpcode { .. }) => {
+                // Illegal opcodes hang the CPU on hardware.
+                self.locked = true;
+                4
+            }
+        };
+
+        t_cycles + self.service_interrupt(memory)
+    }
+
+    /*
+     * Dispatch the highest priority pending interrupt if IME is set.
+     * Clears IME and the interrupt's IF bit, pushes PC and jumps to the interrupt vector.
+     */
+    fn service_interrupt(&mut self, memory: &mut Memory) -> u8 {
+        if self.ime_state != ImeState::Set {
+            return 0;
+        }
+
+        let pending = memory.pending_interrupts();
+        let Some(interrupt) = Interrupt::PRIORITY
+            .into_iter()
+            .find(|interrupt| pending & interrupt.bit() != 0)
+        else {
+            return 0;
+        };
+
+        memory.acknowledge_interrupt(interrupt);
+        self.ime_state = ImeState::Unset;
+        self.low_power_mode = false;
+        self.call(memory, interrupt.vector());
+        20
+    }
+
     fn execute(&mut self, i: Instruction, memory: &mut Memory) -> u8 {
         // Update IME (if we are halfway thru di or ei instruction)
         // Check IME || if halted
         //
 
-        let t_cycle = match i {
+        match i {
             /*
              * Add the value in r8 plus the carry flag to A.
              */
@@ -152,9 +246,9 @@ impl Cpu {
                 let proceed = self.check_condition(condition);
                 if proceed {
                     self.call(memory, n16);
-                    return 12;
+                    return 24;
                 }
-                24
+                12
             }
             /*
              * Complement Carry Flag; C = -C.
@@ -200,17 +294,24 @@ impl Cpu {
              */
             Instruction::DAA => {
                 let a = self.registers.a;
-                // Check & adjust lower nibble
-                if (a & 0x0F) > 9 || self.registers.f.half_carry {
-                    self.registers.a = a.wrapping_add(0x06);
-                    self.registers.f.carry = false;
+                let subtract = self.registers.f.subtract;
+                // After a subtraction only the flags say which nibbles borrowed; after an addition an out of range
+                // digit also needs correcting.
+                let mut correction = 0;
+                if self.registers.f.half_carry || (!subtract && a & 0x0F > 0x09) {
+                    correction |= 0x06;
                 }
-                // Check & adjust upper nibble
-                if (a & 0xF0) > 0x90 || self.registers.f.carry {
-                    self.registers.a = a.wrapping_add(0x60);
-                    self.registers.f.carry = true;
+                if self.registers.f.carry || (!subtract && a > 0x99) {
+                    correction |= 0x60;
                 }
+                self.registers.a = if subtract {
+                    a.wrapping_sub(correction)
+                } else {
+                    a.wrapping_add(correction)
+                };
                 self.registers.f.zero = self.registers.a == 0;
+                self.registers.f.half_carry = false;
+                self.registers.f.carry = correction & 0x60 != 0;
                 4
             }
             /*
@@ -218,12 +319,12 @@ impl Cpu {
              */
             Instruction::DEC_R8 { r8 } => {
                 let r8_val = self.registers.get_r8(&r8);
-                let r8_val_dec = r8_val - 1;
+                let r8_val_dec = r8_val.wrapping_sub(1);
                 self.registers.set_r8(&r8, r8_val_dec);
                 self.registers.f.zero = r8_val_dec == 0;
                 self.registers.f.subtract = true;
-                // Carry from 4th bit required if it flipped
-                self.registers.f.half_carry = r8_val_dec & 0x8 != r8_val & 0x8;
+                // Borrow from the 4th bit required if the lower nibble was 0
+                self.registers.f.half_carry = r8_val & 0xF == 0;
                 4
             }
             /*
@@ -232,11 +333,11 @@ impl Cpu {
             Instruction::DEC_HL_PNTR => {
                 let hl = self.registers.get_hl();
                 let hl_val = memory.read(hl);
-                let hl_val_dec = hl_val - 1;
+                let hl_val_dec = hl_val.wrapping_sub(1);
                 memory.write(hl, hl_val_dec);
                 self.registers.f.zero = hl_val_dec == 0;
                 self.registers.f.subtract = true;
-                self.registers.f.half_carry = hl_val_dec & 0x8 != hl_val & 0x8;
+                self.registers.f.half_carry = hl_val & 0xF == 0;
                 12
             }
             /*
@@ -244,7 +345,7 @@ impl Cpu {
              */
             Instruction::DEC_R16 { r16 } => {
                 let r16_val = self.registers.get_r16(&r16);
-                let r16_val_dec = r16_val - 1;
+                let r16_val_dec = r16_val.wrapping_sub(1);
                 self.registers.set_r16(&r16, r16_val_dec);
                 8
             }
@@ -253,7 +354,7 @@ impl Cpu {
              */
             Instruction::DEC_SP => {
                 let sp_val = self.registers.sp;
-                let sp_val_dec = sp_val - 1;
+                let sp_val_dec = sp_val.wrapping_sub(1);
                 self.registers.sp = sp_val_dec;
                 8
             }
@@ -261,7 +362,7 @@ impl Cpu {
              * Disable Interrupts; clears the IME flag state.
              */
             Instruction::DI => {
-                self.ime_state = ImeState::UNSET;
+                self.ime_state = ImeState::Unset;
                 4
             }
             /*
@@ -273,14 +374,14 @@ impl Cpu {
             }
             /*
              * Enter low power mode until an interrupt occurs.
-             * This behaviour depends on the state of the ime flag.
+             * If IME is clear and an interrupt is already pending, the CPU doesn't halt. Instead, it fails to increment PC
+             * after fetching the next opcode, so the byte following HALT is read twice (the "HALT bug").
              */
             Instruction::HALT => {
-                if self.ime_state == ImeState::SET {
-                    self.low_power_mode = true;
+                if self.ime_state != ImeState::Set && memory.pending_interrupts() != 0 {
+                    self.halt_bug = true;
                 } else {
-                    // Implement interrupt branching
-                    todo!();
+                    self.low_power_mode = true;
                 }
                 4
             }
@@ -289,12 +390,12 @@ impl Cpu {
              */
             Instruction::INC_R8 { r8 } => {
                 let r8_val = self.registers.get_r8(&r8);
-                let r8_val_inc = r8_val + 1;
+                let r8_val_inc = r8_val.wrapping_add(1);
                 self.registers.set_r8(&r8, r8_val_inc);
                 self.registers.f.zero = r8_val_inc == 0;
                 self.registers.f.subtract = false;
                 // Check for 3rd bit overflow
-                self.registers.f.half_carry = r8_val_inc & 0x8 != r8_val & 0x8;
+                self.registers.f.half_carry = r8_val & 0xF == 0xF;
                 4
             }
             /*
@@ -303,11 +404,11 @@ impl Cpu {
             Instruction::INC_HL_PNTR => {
                 let hl = self.registers.get_hl();
                 let hl_val = memory.read(hl);
-                let hl_val_inc = hl_val + 1;
+                let hl_val_inc = hl_val.wrapping_add(1);
                 memory.write(hl, hl_val_inc);
                 self.registers.f.zero = hl_val_inc == 0;
-                self.registers.f.subtract = true;
-                self.registers.f.half_carry = hl_val_inc & 0x8 != hl_val & 0x8;
+                self.registers.f.subtract = false;
+                self.registers.f.half_carry = hl_val & 0xF == 0xF;
                 12
             }
             /*
@@ -315,7 +416,7 @@ impl Cpu {
              */
             Instruction::INC_R16 { r16 } => {
                 let r16_val = self.registers.get_r16(&r16);
-                let r16_val_inc = r16_val + 1;
+                let r16_val_inc = r16_val.wrapping_add(1);
                 self.registers.set_r16(&r16, r16_val_inc);
                 8
             }
@@ -324,7 +425,7 @@ impl Cpu {
              */
             Instruction::INC_SP => {
                 let sp_val = self.registers.sp;
-                let sp_val_inc = sp_val + 1;
+                let sp_val_inc = sp_val.wrapping_add(1);
                 self.registers.sp = sp_val_inc;
                 8
             }
@@ -357,7 +458,6 @@ impl Cpu {
              * Relative jump a certain signed offset away.
              */
             Instruction::JR_N16 { offset } => {
-                // TODO: I'm fairly sure this is completely incorrect. Re-implementation is required.
                 let position = self.position_from_offset(offset);
                 self.jump(position);
                 12
@@ -390,11 +490,10 @@ impl Cpu {
                 8
             }
             /*
-             * Load value n16 into a 16-bit register.
+             * Load value n16 into register r16.
              */
-            Instruction::LD_R16_R16 { dest, src } => {
-                let value = self.registers.get_r16(&src);
-                self.registers.set_r16(&dest, value);
+            Instruction::LD_R16_N16 { r16, n16 } => {
+                self.registers.set_r16(&r16, n16);
                 12
             }
             /*
@@ -441,9 +540,8 @@ impl Cpu {
              * Store value in register A into the byte at address n16, provided n16 is between FF00 & FFFF.
              */
             Instruction::LDH_N16_PNTR_A { n16 } => {
-                if (0xFF00..0xFFFF).contains(&n16) {
-                    let value = memory.read(n16);
-                    self.registers.a = value;
+                if (0xFF00..=0xFFFF).contains(&n16) {
+                    memory.write(n16, self.registers.a);
                 }
                 12
             }
@@ -452,8 +550,7 @@ impl Cpu {
              */
             Instruction::LDH_C_PNTR_A => {
                 let value_address = 0xFF00 + self.registers.c as u16;
-                let value = memory.read(value_address);
-                self.registers.a = value;
+                memory.write(value_address, self.registers.a);
                 8
             }
             /*
@@ -476,7 +573,7 @@ impl Cpu {
              * Load value in register A from the byte at address n16, provided the address is between $FF00 and $FFFF.
              */
             Instruction::LDH_A_N16_PNTR { n16 } => {
-                if (0xFF00..0xFFFF).contains(&n16) {
+                if (0xFF00..=0xFFFF).contains(&n16) {
                     let value = memory.read(n16);
                     self.registers.a = value;
                 }
@@ -551,12 +648,13 @@ impl Cpu {
              */
             Instruction::LD_HL_SPE8 { e8 } => {
                 let sp = self.registers.sp;
-                let value = sp + e8 as i16 as u16;
-                self.registers.set_hl(value);
+                let rhs = e8 as i16 as u16;
+                self.registers.set_hl(sp.wrapping_add(rhs));
                 self.registers.f.zero = false;
                 self.registers.f.subtract = false;
-                self.registers.f.half_carry = (self.registers.sp & 0xF) + (value & 0xF) > 0xF;
-                self.registers.f.carry = (self.registers.sp & 0xFF) + (&0xFF) > 0xFF;
+                // Flags come from the unsigned add of the low byte, as for ADD SP, e8.
+                self.registers.f.half_carry = (sp & 0xF) + (rhs & 0xF) > 0xF;
+                self.registers.f.carry = (sp & 0xFF) + (rhs & 0xFF) > 0xFF;
                 12
             }
             /*
@@ -569,7 +667,7 @@ impl Cpu {
             /*
              * No Operation; do nothing.
              */
-            Instruction::NOP => 1,
+            Instruction::NOP => 4,
             /*
              * Store into A the bitwise OR of the value in r8 and A.
              */
@@ -599,9 +697,9 @@ impl Cpu {
              */
             Instruction::POP_AF => {
                 self.registers.f = registers::Flags::from(memory.read(self.registers.sp));
-                self.registers.sp += 1;
+                self.registers.sp = self.registers.sp.wrapping_add(1);
                 self.registers.a = memory.read(self.registers.sp);
-                self.registers.sp += 1;
+                self.registers.sp = self.registers.sp.wrapping_add(1);
                 12
             }
             /*
@@ -615,10 +713,10 @@ impl Cpu {
                 let upper = r16.get_upper();
                 self.registers
                     .set_r8(&lower, memory.read(self.registers.sp));
-                self.registers.sp += 1;
+                self.registers.sp = self.registers.sp.wrapping_add(1);
                 self.registers
                     .set_r8(&upper, memory.read(self.registers.sp));
-                self.registers.sp += 1;
+                self.registers.sp = self.registers.sp.wrapping_add(1);
                 12
             }
             /*
@@ -628,10 +726,10 @@ impl Cpu {
              * 2. decrement sp; load the value from the f (flag) register to the stack
              */
             Instruction::PUSH_AF => {
-                self.registers.sp -= 1;
+                self.registers.sp = self.registers.sp.wrapping_sub(1);
                 let value = self.registers.a;
                 self.load_u8_into_stack(memory, value);
-                self.registers.sp -= 1;
+                self.registers.sp = self.registers.sp.wrapping_sub(1);
                 let value: u8 = registers::flags_to_u8(&self.registers.f);
                 self.load_u8_into_stack(memory, value);
                 16
@@ -643,11 +741,11 @@ impl Cpu {
              * 2. decrement sp; load the value from the upper nibble of the register to the stack
              */
             Instruction::PUSH_R16 { r16 } => {
-                self.registers.sp -= 1;
+                self.registers.sp = self.registers.sp.wrapping_sub(1);
                 let lower = self.registers.get_r8(&r16.get_lower());
                 let upper = self.registers.get_r8(&r16.get_upper());
                 self.load_u8_into_stack(memory, upper);
-                self.registers.sp -= 1;
+                self.registers.sp = self.registers.sp.wrapping_sub(1);
                 self.load_u8_into_stack(memory, lower);
                 16
             }
@@ -656,21 +754,21 @@ impl Cpu {
              */
             Instruction::RES_U3_R8 { u3, r8 } => {
                 self.res_r8(instructions::U3::get(&u3), r8);
-                2
+                8
             }
             /*
              * Set bit u3 in the byte pointed by HL to 0. Bit 0 is the rightmost one, bit 7 the leftmost one.
              */
             Instruction::RES_U3_HL_PNTR { u3 } => {
                 self.res_hl(memory, instructions::U3::get(&u3), self.registers.get_hl());
-                4
+                16
             }
             /*
              * Return from subroutine; effectively, a POP PC instruction.
              */
             Instruction::RET => {
                 self.ret(memory);
-                12
+                16
             }
             /*
              * Return from subroutine if condition is met.
@@ -688,7 +786,7 @@ impl Cpu {
              * Note: IME is set immediately set subsequent to this instruction.
              */
             Instruction::RETI => {
-                self.ime_state = ImeState::PENDING_INSTRUCTION_COMPLETION;
+                self.ime_state = ImeState::PendingInstructionCompletion;
                 self.ret(memory);
                 16
             }
@@ -751,9 +849,8 @@ impl Cpu {
              * Rotate register A left.
              */
             Instruction::RLCA => {
-                let value = self.rotate_left(self.registers.a);
-                self.registers.a = value;
-                self.registers.f.zero = value == 0;
+                self.registers.a = self.rotate_left(self.registers.a);
+                self.registers.f.zero = false;
                 self.registers.f.subtract = false;
                 self.registers.f.half_carry = false;
                 4
@@ -816,9 +913,8 @@ impl Cpu {
              * Rotate register A right.
              */
             Instruction::RRCA => {
-                let value = self.rotate_right(self.registers.a);
-                self.registers.a = value;
-                self.registers.f.zero = value == 0;
+                self.registers.a = self.rotate_right(self.registers.a);
+                self.registers.f.zero = false;
                 self.registers.f.subtract = false;
                 self.registers.f.half_carry = false;
                 4
@@ -834,43 +930,22 @@ impl Cpu {
              * Subtract the value in r8 and the carry flag from A.
              */
             Instruction::SBC_A_R8 { r8 } => {
-                let c: u8 = if self.registers.f.carry { 1 } else { 0 };
-                let subtrahend = self.registers.get_r8(&r8) + c;
-                let previous = self.registers.a;
-                self.registers.a -= subtrahend;
-                self.registers.f.carry = self.registers.a == 0;
-                self.registers.f.subtract = false;
-                self.registers.f.half_carry = (previous & 0xF) < (subtrahend & 0xF);
-                self.registers.f.carry = previous < subtrahend;
+                self.registers.a = self.sub_a(self.registers.get_r8(&r8), self.registers.f.carry);
                 4
             }
             /*
              * Subtract the byte pointed to by HL and the carry flag from A.
              */
             Instruction::SBC_A_HL_PNTR => {
-                let previous = self.registers.a;
-                let c: u8 = if self.registers.f.carry { 1 } else { 0 };
                 let hl = memory.read(self.registers.get_hl());
-                let subtrahend = hl + c;
-                self.registers.a -= subtrahend;
-                self.registers.f.carry = self.registers.a == 0;
-                self.registers.f.subtract = false;
-                self.registers.f.half_carry = (previous & 0xF) < (subtrahend & 0xF);
-                self.registers.f.carry = previous < subtrahend;
+                self.registers.a = self.sub_a(hl, self.registers.f.carry);
                 8
             }
             /*
              * Subtract the value n8 and the carry flag from A.
              */
             Instruction::SBC_A_N8 { n8 } => {
-                let previous = self.registers.a;
-                let c: u8 = if self.registers.f.carry { 1 } else { 0 };
-                let subtrahend = n8 + c;
-                self.registers.a -= subtrahend;
-                self.registers.f.carry = self.registers.a == 0;
-                self.registers.f.subtract = false;
-                self.registers.f.half_carry = (previous & 0xF) < (subtrahend & 0xF);
-                self.registers.f.carry = previous < subtrahend;
+                self.registers.a = self.sub_a(n8, self.registers.f.carry);
                 8
             }
             /*
@@ -886,7 +961,7 @@ impl Cpu {
              * Set bit u3 in register r8 to 1. Bit 0 is the rightmost one, bit 7 the leftmost one.
              */
             Instruction::SET_U3_R8 { u3, r8 } => {
-                let mask = 1 << (u3.get() - 1);
+                let mask = 1 << u3.get();
                 self.registers
                     .set_r8(&r8, self.registers.get_r8(&r8) | mask);
                 8
@@ -895,9 +970,10 @@ impl Cpu {
              * Set bit u3 in the byte pointed by HL to 1. Bit 0 is the rightmost one, bit 7 the leftmost one.
              */
             Instruction::SET_U3_HL_PNTR { u3 } => {
-                let mask = 1 << (u3.get() - 1);
-                self.registers.set_hl(self.registers.get_hl() | mask);
-                8
+                let mask = 1 << u3.get();
+                let hl = self.registers.get_hl();
+                memory.write(hl, memory.read(hl) | mask);
+                16
             }
             /*
              * Shift Left Arithmetically register r8.
@@ -963,26 +1039,27 @@ impl Cpu {
                 self.registers.f.zero = result == 0;
                 self.registers.f.subtract = false;
                 self.registers.f.half_carry = false;
-                8
+                16
             }
             /*
-             * Enter CPU very low power mode.
+             * Enter CPU very low power mode until a joypad line goes low. DIV is reset.
+             * On CGB, if a speed switch has been armed through KEY1, switch speed instead and pause for 2050 M-cycles.
              */
             Instruction::STOP => {
-                self.very_low_power_mode = true;
+                memory.reset_div();
+                if memory.speed_switch_armed() {
+                    memory.switch_speed();
+                    self.speed_switch_cycles = 2050 * 4;
+                } else {
+                    self.very_low_power_mode = true;
+                }
                 4
             }
             /*
              * Subtract the value in r8 from A.
              */
             Instruction::SUB_A_R8 { r8 } => {
-                let value = self.registers.get_r8(&r8);
-                let previous = self.registers.a;
-                self.registers.a -= value;
-                self.registers.f.zero = self.registers.a == 0;
-                self.registers.f.subtract = true;
-                self.registers.f.half_carry = (value & 0x10) > (previous & 0x10);
-                self.registers.f.carry = value > previous;
+                self.registers.a = self.sub_a(self.registers.get_r8(&r8), false);
                 4
             }
             /*
@@ -990,25 +1067,15 @@ impl Cpu {
              */
             Instruction::SUB_A_HL_PNTR => {
                 let value = memory.read(self.registers.get_hl());
-                let previous = self.registers.a;
-                self.registers.a -= value;
-                self.registers.f.zero = self.registers.a == 0;
-                self.registers.f.subtract = true;
-                self.registers.f.half_carry = (value & 0x10) > (previous & 0x10);
-                self.registers.f.carry = value > previous;
-                4
+                self.registers.a = self.sub_a(value, false);
+                8
             }
             /*
              * Subtract the value n8 from A.
              */
             Instruction::SUB_A_N8 { n8 } => {
-                let previous = self.registers.a;
-                self.registers.a -= n8;
-                self.registers.f.zero = self.registers.a == 0;
-                self.registers.f.subtract = true;
-                self.registers.f.half_carry = (n8 & 0x10) > (previous & 0x10);
-                self.registers.f.carry = n8 > previous;
-                4
+                self.registers.a = self.sub_a(n8, false);
+                8
             }
             /*
              * Swap the upper 4 bits in register r8 and the lower 4 ones.
@@ -1038,7 +1105,7 @@ impl Cpu {
              * Bitwise XOR between the value in r8 and A.
              */
             Instruction::XOR_A_R8 { r8 } => {
-                self.registers.a = self.registers.a ^ self.registers.get_r8(&r8);
+                self.registers.a ^= self.registers.get_r8(&r8);
                 self.registers.f.zero = self.registers.a == 0;
                 self.registers.f.subtract = false;
                 self.registers.f.half_carry = false;
@@ -1049,7 +1116,7 @@ impl Cpu {
              * Bitwise XOR between the byte pointed to by HL and A.
              */
             Instruction::XOR_A_HL_PNTR => {
-                self.registers.a = self.registers.a ^ memory.read(self.registers.get_hl());
+                self.registers.a ^= memory.read(self.registers.get_hl());
                 self.registers.f.zero = self.registers.a == 0;
                 self.registers.f.subtract = false;
                 self.registers.f.half_carry = false;
@@ -1060,22 +1127,23 @@ impl Cpu {
              * Bitwise XOR between the value in n8 and A.
              */
             Instruction::XOR_A_N8 { n8 } => {
-                self.registers.a = self.registers.a ^ n8;
+                self.registers.a ^= n8;
                 self.registers.f.zero = self.registers.a == 0;
                 self.registers.f.subtract = false;
                 self.registers.f.half_carry = false;
                 self.registers.f.carry = false;
                 8
             }
-        };
-        if self.ime_state == ImeState::PENDING_INSTRUCTION_COMPLETION {
+        }
+    }
+    fn update_ime(&mut self) {
+        if self.ime_state == ImeState::PendingInstructionCompletion {
             // We know that we have completed the instruction proceeding the EI call.
-            self.ime_state = ImeState::SET;
-        } else if self.ime_state == ImeState::PENDING_NEW_INSTRUCTION {
+            self.ime_state = ImeState::Set;
+        } else if self.ime_state == ImeState::PendingNewInstruction {
             // EI is called but we must wait another instruction to be called (and also executed).
-            self.ime_state = ImeState::PENDING_INSTRUCTION_COMPLETION;
+            self.ime_state = ImeState::PendingInstructionCompletion;
         }
-        t_cycle
     }
     fn add_a(&mut self, rhs: u8, carry: bool) {
         let (mut sum, mut did_overflow) = self.registers.a.overflowing_add(rhs);
@@ -1132,7 +1200,7 @@ impl Cpu {
             U3::Six => byte & 0b1000000,
             U3::Seven => byte & 0b10000000,
         };
-        self.registers.f.zero = bit != 0;
+        self.registers.f.zero = bit == 0;
         self.registers.f.subtract = false;
         self.registers.f.half_carry = true;
     }
@@ -1140,14 +1208,8 @@ impl Cpu {
         self.registers.pc = n16;
     }
     fn position_from_offset(&mut self, offset: i8) -> u16 {
-        let position = self.registers.pc;
-        if offset > 0 {
-            let cast_offset = offset as u16;
-            return position.wrapping_add(cast_offset);
-        } else {
-            let cast_offset = -offset as u16;
-            return position.wrapping_sub(cast_offset);
-        }
+        // Sign-extending the offset makes negative offsets wrap round to a subtraction.
+        self.registers.pc.wrapping_add(offset as i16 as u16)
     }
     fn check_condition(&mut self, condition: instructions::Condition) -> bool {
         match condition {
@@ -1158,110 +1220,87 @@ impl Cpu {
         }
     }
     fn cp(&mut self, subtrahend: u8) {
-        let result = self.registers.a - subtrahend;
-        self.registers.f.zero = result == 0;
-        self.registers.f.subtract = true;
-        // Isolate lower nibble and check if a carry from the 4th bit was required
-        self.registers.f.half_carry = self.registers.a & 0xF < subtrahend & 0xF;
-        // Check if carry was required for the full operation
-        self.registers.f.carry = subtrahend > self.registers.a;
+        self.sub_a(subtrahend, false);
     }
-    fn set_dec_r16(&mut self, r16: R16) {
-        let r16_val = self.registers.get_r16(&r16);
-        let r16_val_dec = r16_val - 1;
-        self.registers.set_r16(&r16, r16_val_dec);
-        self.registers.f.zero = r16_val_dec == 0;
+    /*
+     * A - rhs (- 1 with carry), setting the flags. The result is returned rather than stored so CP can share it.
+     */
+    fn sub_a(&mut self, rhs: u8, carry: bool) -> u8 {
+        let carry = carry as u8;
+        let difference = self.registers.a.wrapping_sub(rhs).wrapping_sub(carry);
+
+        self.registers.f.zero = difference == 0;
         self.registers.f.subtract = true;
-        // Carry from 4th bit required if it flipped
-        self.registers.f.half_carry = r16_val_dec & 0x8 != r16_val & 0x8;
-    }
-    // todo: rewrite LD instructions to use helper functions
-    fn load_u8_into_R8(&mut self, value: u8, register: R8) {
-        self.registers.set_r8(&register, value);
+        // Isolate lower nibble and check if a borrow from the 4th bit was required
+        self.registers.f.half_carry = (self.registers.a & 0xF) < (rhs & 0xF) + carry;
+        // Check if a borrow was required for the full operation
+        self.registers.f.carry = (self.registers.a as u16) < rhs as u16 + carry as u16;
+
+        difference
     }
     fn load_u8_into_stack(&mut self, memory: &mut Memory, value: u8) {
         let address = self.registers.sp;
         memory.write(address, value);
     }
-    fn load_address_into_reg(&mut self, memory: &mut Memory, address: u16, register: R8) {
-        let value = memory.read(address);
-        self.registers.set_r8(&register, value);
-    }
     fn call(&mut self, memory: &mut Memory, n16: u16) {
-        // push program counter to stack
-        self.registers.sp -= 1;
-        let lower: u8 = (self.registers.pc & 0xF) as u8;
-        let upper: u8 = (self.registers.pc >> 4) as u8;
-        self.load_u8_into_stack(memory, lower);
-        self.registers.sp -= 1;
+        // push program counter to stack, upper byte first
+        let lower: u8 = (self.registers.pc & 0xFF) as u8;
+        let upper: u8 = (self.registers.pc >> 8) as u8;
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
         self.load_u8_into_stack(memory, upper);
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.load_u8_into_stack(memory, lower);
         self.registers.pc = n16;
     }
     fn ret(&mut self, memory: &mut Memory) {
-        let lower: u8 = memory.read(self.registers.pc);
-        self.registers.pc += 1;
-        let upper: u8 = memory.read(self.registers.pc);
-        self.registers.pc += 1;
+        let lower: u8 = memory.read(self.registers.sp);
+        self.registers.sp = self.registers.sp.wrapping_add(1);
+        let upper: u8 = memory.read(self.registers.sp);
+        self.registers.sp = self.registers.sp.wrapping_add(1);
         self.registers.set_pc(lower, upper);
     }
     fn set_ime(&mut self) {
-        if self.ime_state != ImeState::PENDING_NEW_INSTRUCTION
-            || self.ime_state != ImeState::PENDING_INSTRUCTION_COMPLETION
+        if self.ime_state != ImeState::PendingNewInstruction
+            && self.ime_state != ImeState::PendingInstructionCompletion
         {
-            self.ime_state = ImeState::PENDING_NEW_INSTRUCTION;
+            self.ime_state = ImeState::PendingNewInstruction;
         }
     }
     fn rotate_arithmetic_left(&mut self, value: u8) -> u8 {
-        let b7: bool = value & 0x80 == 0;
-        self.registers.f.carry = b7;
-        return value << 1;
+        self.registers.f.carry = value & 0x80 != 0;
+        value << 1
     }
     fn rotate_arithmetic_right(&mut self, value: u8) -> u8 {
         let b7: u8 = value & 0x80;
-        self.registers.f.carry = value & 1 == 0;
-        return (value >> 1) | b7;
+        self.registers.f.carry = value & 1 != 0;
+        (value >> 1) | b7
     }
     fn rotate_logical_right(&mut self, value: u8) -> u8 {
-        let b7: u8 = value & 0x80;
-        self.registers.f.carry = value & 1 == 0;
-        return (value >> 1) | b7;
+        self.registers.f.carry = value & 1 != 0;
+        value >> 1
     }
     fn rotate_left_through_carry(&mut self, value: u8) -> u8 {
-        self.registers.f.carry = value & 0x80 == 0;
-        let mut rot: u8 = value << 1;
-        if self.registers.f.carry {
-            rot = rot | 1;
-        }
-        rot
+        // The old carry rotates into bit 0, so read it before it is replaced by bit 7.
+        let carry_in: u8 = self.registers.f.carry as u8;
+        self.registers.f.carry = value & 0x80 != 0;
+        (value << 1) | carry_in
     }
     fn rotate_left(&mut self, value: u8) -> u8 {
-        let b7: bool = value & 0x80 == 0;
-        self.registers.f.carry = b7;
-        let mut rot: u8 = value << 1;
-        if b7 {
-            rot = rot | 1;
-        }
-        rot
+        self.registers.f.carry = value & 0x80 != 0;
+        value.rotate_left(1)
     }
     fn rotate_right_through_carry(&mut self, value: u8) -> u8 {
-        self.registers.f.carry = value & 1 == 0;
-        let mut rot: u8 = value >> 1;
-        if self.registers.f.carry {
-            rot = rot | 0x80;
-        }
-        rot
+        // The old carry rotates into bit 7, so read it before it is replaced by bit 0.
+        let carry_in: u8 = (self.registers.f.carry as u8) << 7;
+        self.registers.f.carry = value & 1 != 0;
+        (value >> 1) | carry_in
     }
     fn rotate_right(&mut self, value: u8) -> u8 {
-        let b0: bool = value & 1 == 0;
-        self.registers.f.carry = b0;
-        let mut rot: u8 = value >> 1;
-        if b0 {
-            rot = rot | 0x80;
-        }
-        rot
+        self.registers.f.carry = value & 1 != 0;
+        value.rotate_right(1)
     }
     fn or_a(&mut self, rhs: u8) {
-        self.registers.a = self.registers.a | rhs;
+        self.registers.a |= rhs;
         self.registers.f.zero = self.registers.a == 0;
         self.registers.f.subtract = false;
         self.registers.f.half_carry = false;
@@ -1272,24 +1311,16 @@ impl Cpu {
         let mask = !(1 << bit);
         let value = self.registers.get_r8(&r8) & mask;
         self.registers.set_r8(&r8, value);
-        self.registers.f.zero = false;
-        self.registers.f.subtract = false;
-        self.registers.f.half_carry = false;
-        self.registers.f.carry = false;
     }
 
     fn res_hl(&mut self, memory: &mut Memory, bit: u8, hl_ref: u16) {
         let mask = !(1 << bit);
         let hl = memory.read(hl_ref);
         memory.write(hl_ref, hl & mask);
-        self.registers.f.zero = false;
-        self.registers.f.subtract = false;
-        self.registers.f.half_carry = false;
-        self.registers.f.carry = false;
     }
     fn swap(&mut self, value: u8) -> u8 {
         let upper = (value >> 4) & 0xF;
         let lower = value & 0xF;
-        return (lower << 4) | upper;
+        (lower << 4) | upper
     }
 }
@@ -28,10 +28,10 @@ impl From<&Flags> for u8 {
 }
 
 pub(crate) fn flags_to_u8(flags: &Flags) -> u8 {
-    return ((if flags.zero { 1 } else { 0 }) << 7)
+    ((if flags.zero { 1 } else { 0 }) << 7)
         | ((if flags.subtract { 1 } else { 0 }) << 6)
         | ((if flags.half_carry { 1 } else { 0 }) << 5)
-        | ((if flags.carry { 1 } else { 0 }) << 4);
+        | ((if flags.carry { 1 } else { 0 }) << 4)
 }
 pub(crate) struct Registers {
     pub(crate) a: u8,
@@ -47,40 +47,33 @@ pub(crate) struct Registers {
 }
 
 impl Registers {
+    // Register state left behind by the DMG boot ROM, as execution begins at the cartridge entry point.
     pub(crate) fn new() -> Self {
         Self {
-            a: 0,
-            b: 0,
-            c: 0,
-            d: 0,
-            e: 0,
+            a: 0x01,
+            b: 0x00,
+            c: 0x13,
+            d: 0x00,
+            e: 0xD8,
             f: Flags {
-                zero: false,
+                zero: true,
                 subtract: false,
-                half_carry: false,
-                carry: false,
+                half_carry: true,
+                carry: true,
             },
-            h: 0,
-            l: 0,
-            sp: 0,
-            pc: 0,
+            h: 0x01,
+            l: 0x4D,
+            sp: 0xFFFE,
+            pc: 0x0100,
         }
     }
 
-    fn get_af(&self) -> u16 {
-        ((self.a as u16) << 8) & u8::from(&self.f) as u16
-    }
-
-    fn set_af(&mut self, value: u16) {
-        self.a = (value >> 8) as u8;
-        self.f = Flags::from((value & 0xFF) as u8);
-    }
     pub(crate) fn set_pc(&mut self, lower: u8, upper: u8) {
-        self.pc = ((upper << 4) | lower) as u16;
+        self.pc = ((upper as u16) << 8) | lower as u16;
     }
 
     fn get_bc(&self) -> u16 {
-        ((self.b as u16) << 8) & self.c as u16
+        ((self.b as u16) << 8) | self.c as u16
     }
 
     fn set_bc(&mut self, value: u16) {
@@ -89,7 +82,7 @@ impl Registers {
     }
 
     fn get_de(&self) -> u16 {
-        ((self.d as u16) << 8) & self.e as u16
+        ((self.d as u16) << 8) | self.e as u16
     }
 
     fn set_de(&mut self, value: u16) {
@@ -98,7 +91,7 @@ impl Registers {
     }
 
     pub(crate) fn get_hl(&self) -> u16 {
-        ((self.h as u16) << 8) & self.l as u16
+        ((self.h as u16) << 8) | self.l as u16
     }
 
     pub(crate) fn set_hl(&mut self, value: u16) {
@@ -0,0 +1,244 @@
+use crate::cartridge::header::{self, CartridgeType};
+use crate::cartridge::USE_NEW_LICENSEE;
+use crate::rom;
+use std::ops::Range;
+use std::path::PathBuf;
+
+const USAGE: &str = "Usage: gameboy fix [--validate] [--logo] [--header-checksum] [--global-checksum] [--title <title>] \
+                     [--cgb none|enhanced|only] [--sgb] [--no-sgb] [--type <type>] [--ram-size <code>] [--dry-run] <rom>";
+
+// Header fields by location, to say what each changed byte belongs to.
+const FIELDS: [(Range<usize>, &str); 13] = [
+    (0x104..0x134, "Nintendo logo"),
+    (0x134..0x13F, "title"),
+    (0x13F..0x143, "title/manufacturer code"),
+    (0x143..0x144, "CGB flag"),
+    (0x144..0x146, "new licensee code"),
+    (0x146..0x147, "SGB flag"),
+    (0x147..0x148, "cartridge type"),
+    (0x148..0x149, "ROM size"),
+    (0x149..0x14A, "RAM size"),
+    (0x14A..0x14B, "destination code"),
+    (0x14B..0x14C, "old licensee code"),
+    (0x14D..0x14E, "header checksum"),
+    (0x14E..0x150, "global checksum"),
+];
+
+/*
+ * What to rewrite. Fields left as None are kept as they are.
+ */
+#[derive(Default)]
+struct Fixes {
+    logo: bool,
+    header_checksum: bool,
+    global_checksum: bool,
+    title: Option<String>,
+    cgb_flag: Option<u8>,
+    sgb: Option<bool>,
+    cartridge_type: Option<u8>,
+    ram_size_code: Option<u8>,
+}
+
+/*
+ * `gameboy fix`: patch a linked ROM's header in place, like rgbfix. With --dry-run the file is left alone and the bytes
+ * that would change are listed instead.
+ */
+pub(crate) fn run(args: impl Iterator<Item = String>) {
+    let mut fixes = Fixes::default();
+    let mut dry_run = false;
+    let mut rom_path = None;
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--validate" => {
+                fixes.logo = true;
+                fixes.header_checksum = true;
+                fixes.global_checksum = true;
+            }
+            "--logo" => fixes.logo = true,
+            "--header-checksum" => fixes.header_checksum = true,
+            "--global-checksum" => fixes.global_checksum = true,
+            "--title" => fixes.title = Some(args.next().expect(USAGE)),
+            "--cgb" => fixes.cgb_flag = Some(parse_cgb_flag(&args.next().expect(USAGE))),
+            "--sgb" => fixes.sgb = Some(true),
+            "--no-sgb" => fixes.sgb = Some(false),
+            "--type" => {
+                fixes.cartridge_type = Some(parse_cartridge_type(&args.next().expect(USAGE)))
+            }
+            "--ram-size" => fixes.ram_size_code = Some(parse_ram_size(&args.next().expect(USAGE))),
+            "--dry-run" => dry_run = true,
+            _ => rom_path = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let Some(rom_path) = rom_path else {
+        panic!("{}", USAGE);
+    };
+    let original = std::fs::read(&rom_path).unwrap_or_else(|e| {
+        panic!("Could not read {}: {}", rom_path.display(), e);
+    });
+    if rom::is_archive(&original) {
+        panic!(
+            "{} is an archive; extract the ROM to fix it",
+            rom_path.display()
+        );
+    }
+    if original.len() < header::HEADER_END {
+        panic!(
+            "{}",
+            header::HeaderError::TooShort {
+                length: original.len()
+            }
+        );
+    }
+
+    let mut rom = original.clone();
+    for warning in apply(&mut rom, &fixes) {
+        eprintln!("Warning: {}", warning);
+    }
+
+    let changes = diff(&original, &rom);
+    if dry_run {
+        for line in &changes {
+            println!("{}", line);
+        }
+        if changes.is_empty() {
+            println!("No changes");
+        }
+        return;
+    }
+    if changes.is_empty() {
+        return;
+    }
+
+    // Replaced in one step so a failed write can't leave a truncated ROM behind.
+    let temporary = rom_path.with_extension("fix.tmp");
+    std::fs::write(&temporary, &rom)
+        .and_then(|()| std::fs::rename(&temporary, &rom_path))
+        .unwrap_or_else(|e| {
+            panic!("Could not write {}: {}", rom_path.display(), e);
+        });
+    eprintln!("Changed {} bytes in {}", changes.len(), rom_path.display());
+}
+
+/*
+ * Rewrite the requested fields. The checksums are done last so they cover everything else that changed.
+ */
+fn apply(rom: &mut [u8], fixes: &Fixes) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if let Some(cgb_flag) = fixes.cgb_flag {
+        rom[0x143] = cgb_flag;
+    }
+    if let Some(title) = &fixes.title {
+        // The CGB flag takes the title's last byte.
+        let length = if header::is_cgb(rom[0x143]) { 15 } else { 16 };
+        if !title.is_ascii() {
+            warnings.push(format!("title {:?} isn't ASCII", title));
+        }
+        let bytes = title.as_bytes();
+        if bytes.len() > length {
+            warnings.push(format!("title {:?} truncated to {} bytes", title, length));
+        }
+        let field = &mut rom[0x134..0x134 + length];
+        field.fill(0);
+        let copied = bytes.len().min(length);
+        field[..copied].copy_from_slice(&bytes[..copied]);
+    }
+    if let Some(sgb) = fixes.sgb {
+        rom[0x146] = if sgb { 0x03 } else { 0x00 };
+        if sgb && rom[0x14B] != USE_NEW_LICENSEE {
+            warnings.push(format!(
+                "SGB flag set, but the old licensee code is ${:02X} rather than $33 so it will be ignored",
+                rom[0x14B]
+            ));
+        }
+    }
+    if let Some(cartridge_type) = fixes.cartridge_type {
+        rom[0x147] = cartridge_type;
+    }
+    if let Some(ram_size_code) = fixes.ram_size_code {
+        rom[0x149] = ram_size_code;
+    }
+    if fixes.logo {
+        rom[header::LOGO_START..header::LOGO_START + header::NINTENDO_LOGO.len()]
+            .copy_from_slice(&header::NINTENDO_LOGO);
+    }
+    if fixes.header_checksum {
+        rom[0x14D] = header::checksum(rom);
+    } else if rom[0x14D] != header::checksum(rom) {
+        warnings.push(
+            "header checksum doesn't match, so the boot ROM will refuse the cartridge".to_string(),
+        );
+    }
+    if fixes.global_checksum {
+        let checksum = header::global_checksum(rom);
+        rom[0x14E..0x150].copy_from_slice(&checksum.to_be_bytes());
+    }
+
+    warnings
+}
+
+/*
+ * One line per changed byte, naming the header field it belongs to.
+ */
+fn diff(before: &[u8], after: &[u8]) -> Vec<String> {
+    before
+        .iter()
+        .zip(after)
+        .enumerate()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(address, (before, after))| {
+            let field = FIELDS
+                .iter()
+                .find(|(range, _)| range.contains(&address))
+                .map_or("", |(_, name)| name);
+            format!(
+                "${:04X}: ${:02X} -> ${:02X}  {}",
+                address, before, after, field
+            )
+        })
+        .collect()
+}
+
+fn parse_cgb_flag(value: &str) -> u8 {
+    match value {
+        "none" => 0x00,
+        "enhanced" => 0x80,
+        "only" => 0xC0,
+        _ => panic!("{}", USAGE),
+    }
+}
+
+/*
+ * A cartridge type as a byte or by name, e.g. `0x1B`, `$1B` or `MBC5+RAM+BATTERY`.
+ */
+fn parse_cartridge_type(value: &str) -> u8 {
+    if let Some(byte) = parse_byte(value) {
+        return byte;
+    }
+    (0..=0xFF)
+        .find(|&byte| {
+            CartridgeType::from_byte(byte).is_some_and(|cartridge_type| {
+                cartridge_type.to_string().eq_ignore_ascii_case(value)
+            })
+        })
+        .unwrap_or_else(|| panic!("Unknown cartridge type {}", value))
+}
+
+/*
+ * A RAM size code, as stored at 0x149.
+ */
+fn parse_ram_size(value: &str) -> u8 {
+    parse_byte(value)
+        .filter(|&code| header::ram_size(code).is_some())
+        .unwrap_or_else(|| panic!("Unknown RAM size code {}", value))
+}
+
+fn parse_byte(value: &str) -> Option<u8> {
+    if let Some(hex) = value.strip_prefix("0x").or_else(|| value.strip_prefix('$')) {
+        u8::from_str_radix(hex, 16).ok()
+    } else {
+        value.parse().ok()
+    }
+}
@@ -0,0 +1,290 @@
+use crate::cartridge::header::{self, DestinationCode};
+use crate::cartridge::{self, Licensee, Support};
+use crate::rom;
+use sha1::{Digest, Sha1};
+use std::fmt::Write;
+use std::path::PathBuf;
+
+const USAGE: &str = "Usage: gameboy info [--json] <rom>";
+
+/*
+ * A header field as printed: bytes and words show in hex as text but stay numbers in JSON.
+ */
+enum Value {
+    Text(String),
+    Number(usize),
+    Byte(u8),
+    Word(u16),
+    Bool(bool),
+    List(Vec<String>),
+    Missing,
+}
+
+struct Field {
+    key: &'static str,
+    label: &'static str,
+    value: Value,
+}
+
+/*
+ * `gameboy info`: print everything the header says about a ROM, whether its checksums hold, how well its mapper is
+ * emulated and its hashes, either for reading or as a JSON object for scripts.
+ */
+pub(crate) fn run(args: impl Iterator<Item = String>) {
+    let mut json = false;
+    let mut rom_path = None;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ => rom_path = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let Some(rom_path) = rom_path else {
+        panic!("{}", USAGE);
+    };
+    let rom = rom::load(&rom_path).unwrap_or_else(|e| {
+        panic!("Could not load {}: {}", rom_path.display(), e);
+    });
+
+    let fields = describe(&rom_path.display().to_string(), &rom);
+    if json {
+        println!("{}", to_json(&fields));
+    } else {
+        print!("{}", to_text(&fields));
+    }
+}
+
+fn describe(path: &str, rom: &[u8]) -> Vec<Field> {
+    let (header, warnings) = header::parse_lenient(rom);
+    let mut fields = Vec::new();
+    let mut field = |key, label, value| fields.push(Field { key, label, value });
+
+    field("file", "File", Value::Text(path.to_string()));
+    field("size", "Size", Value::Number(rom.len()));
+    field("title", "Title", Value::Text(header.title()));
+    // Only later carts have a manufacturer code; on older ones these bytes are the end of the title.
+    let manufacturer_code = header.manufacturer_code;
+    field(
+        "manufacturer_code",
+        "Manufacturer code",
+        if manufacturer_code
+            .iter()
+            .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit())
+        {
+            Value::Text(String::from_utf8_lossy(&manufacturer_code).into_owned())
+        } else {
+            Value::Missing
+        },
+    );
+    field(
+        "old_licensee_code",
+        "Old licensee code",
+        Value::Byte(header.old_licensee_code),
+    );
+    // The new code is only in use when the old one says so.
+    let new_licensee_code = match header.licensee {
+        Licensee::New(_) => {
+            Value::Text(String::from_utf8_lossy(&header.new_licensee_code).into_owned())
+        }
+        Licensee::Old(_) => Value::Missing,
+    };
+    field("new_licensee_code", "New licensee code", new_licensee_code);
+    field(
+        "licensee",
+        "Licensee",
+        header
+            .licensee
+            .name()
+            .map_or(Value::Missing, |name| Value::Text(name.to_string())),
+    );
+    field("cgb_flag", "CGB flag", Value::Byte(header.cgb_flag));
+    let cgb = match header.cgb_flag {
+        0xC0 => "only",
+        0x80 => "enhanced",
+        _ => "none",
+    };
+    field("cgb", "CGB support", Value::Text(cgb.to_string()));
+    field("sgb", "SGB support", Value::Bool(header.sgb));
+    field(
+        "cartridge_type_code",
+        "Cartridge type code",
+        Value::Byte(header.cartridge_type_code),
+    );
+    field(
+        "cartridge_type",
+        "Cartridge type",
+        Value::Text(header.cartridge_type.to_string()),
+    );
+    let (support, support_notes) = match cartridge::support(header.cartridge_type) {
+        Support::Full => ("full", Value::Missing),
+        Support::Partial(notes) => ("partial", Value::Text(notes.to_string())),
+        Support::Unsupported => ("unsupported", Value::Missing),
+    };
+    field(
+        "mapper_support",
+        "Mapper support",
+        Value::Text(support.to_string()),
+    );
+    field("mapper_notes", "Mapper notes", support_notes);
+    field(
+        "rom_size_code",
+        "ROM size code",
+        Value::Byte(header.rom_size_code),
+    );
+    field(
+        "rom_size",
+        "ROM size",
+        header.rom_size.map_or(Value::Missing, Value::Number),
+    );
+    field(
+        "rom_banks",
+        "ROM banks",
+        header.num_rom_banks.map_or(Value::Missing, Value::Number),
+    );
+    field(
+        "ram_size_code",
+        "RAM size code",
+        Value::Byte(header.ram_size_code),
+    );
+    field(
+        "ram_size",
+        "RAM size",
+        header.ram_size.map_or(Value::Missing, Value::Number),
+    );
+    let destination = match header.destination_code {
+        DestinationCode::Japanese => Value::Text("Japanese".to_string()),
+        DestinationCode::Overseas => Value::Text("overseas".to_string()),
+        DestinationCode::Unknown(_) => Value::Missing,
+    };
+    field("destination", "Destination", destination);
+    field("version", "Version", Value::Byte(header.version_number));
+
+    field(
+        "header_checksum",
+        "Header checksum",
+        Value::Byte(header.header_checksum),
+    );
+    let expected = (rom.len() >= header::HEADER_END).then(|| header::checksum(rom));
+    field(
+        "header_checksum_expected",
+        "Expected header checksum",
+        expected.map_or(Value::Missing, Value::Byte),
+    );
+    field(
+        "header_checksum_ok",
+        "Header checksum OK",
+        Value::Bool(expected == Some(header.header_checksum)),
+    );
+    let stored = header::stored_global_checksum(rom);
+    let expected = header::global_checksum(rom);
+    field("global_checksum", "Global checksum", Value::Word(stored));
+    field(
+        "global_checksum_expected",
+        "Expected global checksum",
+        Value::Word(expected),
+    );
+    field(
+        "global_checksum_ok",
+        "Global checksum OK",
+        Value::Bool(stored == expected),
+    );
+    let logo_ok = !header::diagnose(rom)
+        .iter()
+        .any(|diagnostic| matches!(diagnostic, header::Diagnostic::LogoMismatch { .. }));
+    field("logo_ok", "Nintendo logo OK", Value::Bool(logo_ok));
+    field(
+        "boot_rom_accepts",
+        "Boot ROM accepts",
+        Value::Bool(header::boot_rom_accepts(rom, header.cgb)),
+    );
+    field(
+        "warnings",
+        "Warnings",
+        Value::List(warnings.iter().map(ToString::to_string).collect()),
+    );
+
+    field(
+        "crc32",
+        "CRC32",
+        Value::Text(format!("{:08x}", crc32fast::hash(rom))),
+    );
+    field(
+        "sha1",
+        "SHA-1",
+        Value::Text(format!("{:x}", Sha1::digest(rom))),
+    );
+    fields
+}
+
+fn to_text(fields: &[Field]) -> String {
+    let width = fields
+        .iter()
+        .map(|field| field.label.len())
+        .max()
+        .unwrap_or(0);
+    let mut text = String::new();
+    for field in fields {
+        let value = match &field.value {
+            Value::Text(text) => text.clone(),
+            Value::Number(number) => number.to_string(),
+            Value::Byte(byte) => format!("${:02X}", byte),
+            Value::Word(word) => format!("${:04X}", word),
+            Value::Bool(true) => "yes".to_string(),
+            Value::Bool(false) => "no".to_string(),
+            Value::List(items) if items.is_empty() => "none".to_string(),
+            Value::List(items) => items.join("; "),
+            Value::Missing => "-".to_string(),
+        };
+        writeln!(
+            text,
+            "{:width$}  {}",
+            format!("{}:", field.label),
+            value,
+            width = width + 1
+        )
+        .unwrap();
+    }
+    text
+}
+
+fn to_json(fields: &[Field]) -> String {
+    let members: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            let value = match &field.value {
+                Value::Text(text) => json_string(text),
+                Value::Number(number) => number.to_string(),
+                Value::Byte(byte) => byte.to_string(),
+                Value::Word(word) => word.to_string(),
+                Value::Bool(bool) => bool.to_string(),
+                Value::List(items) => format!(
+                    "[{}]",
+                    items
+                        .iter()
+                        .map(|item| json_string(item))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                Value::Missing => "null".to_string(),
+            };
+            format!("  {}: {}", json_string(field.key), value)
+        })
+        .collect();
+    format!("{{\n{}\n}}", members.join(",\n"))
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
@@ -0,0 +1,39 @@
+#[derive(Clone, Copy)]
+pub(crate) enum Interrupt {
+    VBlank,
+    Stat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    // Highest priority first, as serviced by the CPU.
+    pub(crate) const PRIORITY: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::Stat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    pub(crate) fn bit(&self) -> u8 {
+        match self {
+            Interrupt::VBlank => 0b00001,
+            Interrupt::Stat => 0b00010,
+            Interrupt::Timer => 0b00100,
+            Interrupt::Serial => 0b01000,
+            Interrupt::Joypad => 0b10000,
+        }
+    }
+
+    pub(crate) fn vector(&self) -> u16 {
+        match self {
+            Interrupt::VBlank => 0x40,
+            Interrupt::Stat => 0x48,
+            Interrupt::Timer => 0x50,
+            Interrupt::Serial => 0x58,
+            Interrupt::Joypad => 0x60,
+        }
+    }
+}
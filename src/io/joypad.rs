@@ -1,17 +1,74 @@
+#[derive(Clone, Copy)]
+pub(crate) enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    // Lower nibble is the d-pad group, upper nibble the button group.
+    fn mask(&self) -> u8 {
+        match self {
+            Button::Right => 0b00000001,
+            Button::Left => 0b00000010,
+            Button::Up => 0b00000100,
+            Button::Down => 0b00001000,
+            Button::A => 0b00010000,
+            Button::B => 0b00100000,
+            Button::Select => 0b01000000,
+            Button::Start => 0b10000000,
+        }
+    }
+}
+
 pub(crate) struct Joypad {
     data: u8,
+    pressed: u8,
 }
 
 impl Joypad {
     pub(crate) fn new() -> Self {
-        Self { data: 0x0F }
+        Self {
+            data: 0x0F,
+            pressed: 0,
+        }
     }
 
     pub(crate) fn get(&self) -> u8 {
-        self.data | 0xF
+        // Lines are active low, as are the group selects in bits 4 (d-pad) and 5 (buttons).
+        let mut lines = 0xF;
+        if self.data & 0x10 == 0 {
+            lines &= !(self.pressed & 0xF);
+        }
+        if self.data & 0x20 == 0 {
+            lines &= !(self.pressed >> 4);
+        }
+        0xC0 | (self.data & 0x30) | lines
     }
 
     pub(crate) fn set(&mut self, data: u8) {
         self.data = data & 0xF0;
     }
+
+    /*
+     * Press or release a button. Returns true if a selected line went from high to low.
+     */
+    pub(crate) fn set_pressed(&mut self, button: Button, pressed: bool) -> bool {
+        let previous = self.get();
+        if pressed {
+            self.pressed |= button.mask();
+        } else {
+            self.pressed &= !button.mask();
+        }
+        previous & !self.get() & 0xF != 0
+    }
+
+    pub(crate) fn any_line_low(&self) -> bool {
+        self.get() & 0xF != 0xF
+    }
 }
@@ -1,5 +1,9 @@
+mod interrupts;
 mod joypad;
+mod speed;
 mod timers;
 
+pub(crate) use interrupts::*;
 pub(crate) use joypad::*;
+pub(crate) use speed::*;
 pub(crate) use timers::*;
@@ -0,0 +1,37 @@
+/*
+ * CGB speed switch (KEY1). Arming bit 0 and executing STOP toggles double-speed mode.
+ */
+pub(crate) struct Speed {
+    double_speed: bool,
+    switch_armed: bool,
+}
+
+impl Speed {
+    pub(crate) fn new() -> Self {
+        Self {
+            double_speed: false,
+            switch_armed: false,
+        }
+    }
+
+    pub(crate) fn get(&self) -> u8 {
+        0x7E | ((self.double_speed as u8) << 7) | self.switch_armed as u8
+    }
+
+    pub(crate) fn set(&mut self, byte: u8) {
+        self.switch_armed = byte & 0b1 != 0;
+    }
+
+    pub(crate) fn is_double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    pub(crate) fn is_switch_armed(&self) -> bool {
+        self.switch_armed
+    }
+
+    pub(crate) fn switch(&mut self) {
+        self.double_speed = !self.double_speed;
+        self.switch_armed = false;
+    }
+}
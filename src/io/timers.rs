@@ -24,7 +24,7 @@ impl Timers {
         self.internal_divider += ticks;
 
         while self.internal_divider >= 256 {
-            self.divider.wrapping_add(1);
+            self.divider = self.divider.wrapping_add(1);
             self.internal_divider -= 256;
         }
 
@@ -1,30 +1,312 @@
+use audio::Audio;
+use cartridge::{header, PngImage};
 use cpu::Cpu;
+use io::Button;
 use memory::Memory;
-use minifb::{Scale, Window, WindowOptions};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Scale, Window, WindowOptions};
+use ppu::{Renderer, HEIGHT, WIDTH};
+use save::SaveFile;
+use std::path::PathBuf;
+use wav::Recording;
 
+mod apu;
+mod audio;
 mod cartridge;
 mod cpu;
+mod fix;
+mod info;
 mod io;
 mod memory;
+mod patch;
+mod ppu;
+mod rom;
+mod save;
+mod wav;
 
-const WIDTH: usize = 160;
-const HEIGHT: usize = 144;
+// 4.194304 MHz / 59.73 Hz
+const T_CYCLES_PER_FRAME: usize = 70224;
+
+const KEY_MAP: [(Key, Button); 8] = [
+    (Key::Right, Button::Right),
+    (Key::Left, Button::Left),
+    (Key::Up, Button::Up),
+    (Key::Down, Button::Down),
+    (Key::Z, Button::A),
+    (Key::X, Button::B),
+    (Key::Backspace, Button::Select),
+    (Key::Enter, Button::Start),
+];
+
+// Tilt for carts with an accelerometer, as (key, x, y). Holding the left mouse button tilts towards the pointer instead.
+const TILT_KEYS: [(Key, f32, f32); 4] = [
+    (Key::J, -1.0, 0.0),
+    (Key::L, 1.0, 0.0),
+    (Key::I, 0.0, -1.0),
+    (Key::K, 0.0, 1.0),
+];
+
+// How often battery saves are written out while the game is writing to cartridge RAM, about every 5 seconds.
+const FRAMES_PER_SAVE_FLUSH: usize = 300;
+
+// Starts and stops a WAV recording.
+const RECORD_KEY: Key = Key::F9;
+
+const USAGE: &str = "\
+Usage: gameboy [--fifo] [--record <wav>] [--record-channels] [--camera <png>] [--lenient] [--boot-check]
+               [--patch <ips|ups|bps>]... [--no-patch] <rom>
+       gameboy info [--json] <rom>
+       gameboy fix [options] <rom>
+
+Sound needs a build with `--features audio`; otherwise the emulator runs silently.";
 
 fn main() {
-    let cpu = Cpu::new();
-    let memory = Memory::new(Vec::new());
+    let mut args = std::env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("info") => return info::run(args.skip(1)),
+        Some("fix") => return fix::run(args.skip(1)),
+        _ => {}
+    }
+
+    let mut rom_path = None;
+    let mut renderer = Renderer::Scanline;
+    let mut record_path = None;
+    let mut record_channels = false;
+    let mut camera_image = None;
+    let mut lenient = false;
+    let mut boot_check = false;
+    let mut patch_paths = Vec::new();
+    let mut auto_patch = true;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fifo" => renderer = Renderer::PixelFifo,
+            "--record" => record_path = Some(PathBuf::from(args.next().expect(USAGE))),
+            "--record-channels" => record_channels = true,
+            "--camera" => camera_image = Some(PathBuf::from(args.next().expect(USAGE))),
+            "--lenient" => lenient = true,
+            "--boot-check" => boot_check = true,
+            "--patch" => patch_paths.push(PathBuf::from(args.next().expect(USAGE))),
+            "--no-patch" => auto_patch = false,
+            _ => rom_path = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let Some(rom_path) = rom_path else {
+        panic!("{}", USAGE);
+    };
+    let mut rom = rom::load(&rom_path).unwrap_or_else(|e| {
+        panic!("Could not load {}: {}", rom_path.display(), e);
+    });
+
+    // Patches are applied to the copy in memory only; the ROM file is never written.
+    if patch_paths.is_empty() && auto_patch {
+        patch_paths = patch::discover(&rom_path);
+    }
+    for path in patch_paths {
+        let data = std::fs::read(&path).unwrap_or_else(|e| {
+            panic!("Could not read {}: {}", path.display(), e);
+        });
+        rom = patch::apply(&rom, &data).unwrap_or_else(|e| {
+            panic!("Could not apply {}: {}", path.display(), e);
+        });
+        eprintln!("Applied {}", path.display());
+    }
+
+    // Homebrew often has sloppy headers, so lenient mode runs anything and only warns.
+    let header = if lenient {
+        let (header, warnings) = header::parse_lenient(&rom);
+        for warning in warnings {
+            eprintln!("Header: {}", warning);
+        }
+        header
+    } else {
+        header::parse(&rom).unwrap_or_else(|e| {
+            panic!(
+                "Bad header in {}: {} (--lenient runs it anyway)",
+                rom_path.display(),
+                e
+            );
+        })
+    };
+    for diagnostic in header::diagnose(&rom) {
+        eprintln!("ROM: {}", diagnostic);
+    }
+    let title = format!("gameboy - {}", header.title());
+
+    let mut cpu = Cpu::new();
+    // Without a boot ROM to run, emulate its verdict: a cartridge it would reject just hangs.
+    if boot_check && !header::boot_rom_accepts(&rom, header.cgb) {
+        eprintln!("Boot ROM check failed, locking up");
+        cpu.lock();
+    }
+    let mut memory = Memory::new(rom);
+    memory.set_renderer(renderer);
+    if let Some(path) = camera_image {
+        match PngImage::open(&path) {
+            Ok(image) => memory.set_image_source(Box::new(image)),
+            Err(e) => eprintln!("Could not load {}: {}", path.display(), e),
+        }
+    }
+
+    let mut save_file = SaveFile::new(&rom_path);
+    if let Err(e) = save_file.load(&mut memory) {
+        eprintln!("Could not load {}: {}", save_file.path().display(), e);
+    }
+    let mut frames_since_flush = 0;
+
+    let mut audio = Audio::open();
+    memory.set_sample_rate(audio.sample_rate());
+
+    let mut recording = None;
+    let mut rumbling = false;
+    let mut buzzing = false;
+    if let Some(path) = record_path {
+        recording = start_recording(&mut memory, &audio, &path, record_channels);
+    }
 
-    let mut buffer: Vec<u32> = vec![0x0; WIDTH * HEIGHT];
     let opts = WindowOptions {
         scale: Scale::X4,
         ..WindowOptions::default()
     };
-    let mut window = Window::new("gameboy", WIDTH, HEIGHT, opts).unwrap_or_else(|e| {
+    let mut window = Window::new(&title, WIDTH, HEIGHT, opts).unwrap_or_else(|e| {
         panic!("{}", e);
     });
 
     window.set_target_fps(60);
     while window.is_open() {
-        window.update_with_buffer(&buffer, WIDTH, HEIGHT).unwrap();
+        for (key, button) in KEY_MAP {
+            memory.set_button(button, window.is_key_down(key));
+        }
+        let (x, y) = tilt(&window);
+        memory.set_tilt(x, y);
+        if window.is_key_pressed(RECORD_KEY, KeyRepeat::No) {
+            recording = match recording.take() {
+                Some(recording) => {
+                    stop_recording(&mut memory, recording);
+                    None
+                }
+                None => {
+                    let path = wav::next_recording_path(&rom_path);
+                    start_recording(&mut memory, &audio, &path, record_channels)
+                }
+            };
+        }
+
+        let mut t_cycles = 0;
+        while t_cycles < T_CYCLES_PER_FRAME {
+            let step_cycles = cpu.step(&mut memory);
+            t_cycles += memory.tick(step_cycles) as usize;
+        }
+
+        let samples = memory.take_samples();
+        audio.queue(&samples);
+        if let Some(active) = &mut recording {
+            let channels = memory.take_channel_samples();
+            if !active.has_room(samples.len()) {
+                eprintln!(
+                    "Recording to {} reached the 4 GiB WAV limit",
+                    active.path().display()
+                );
+                stop_recording(&mut memory, recording.take().unwrap());
+            } else if let Err(e) = active.write(&samples, &channels) {
+                eprintln!("Recording to {} failed: {}", active.path().display(), e);
+                // Finishing keeps whatever was written before the failure playable.
+                stop_recording(&mut memory, recording.take().unwrap());
+            }
+        }
+        // Rate control would make recordings depend on the host, so it is paused while recording.
+        if recording.is_none() {
+            memory.set_sample_rate(audio.sample_rate());
+        }
+
+        // Cartridge feedback the host can't reproduce is shown in the title bar.
+        let rumble_event = memory.rumble_event();
+        let buzzer_event = memory.buzzer_event();
+        if rumble_event.is_some() || buzzer_event.is_some() {
+            rumbling = rumble_event.unwrap_or(rumbling);
+            buzzing = buzzer_event.unwrap_or(buzzing);
+            let mut title = title.clone();
+            if rumbling {
+                title.push_str(" (rumble)");
+            }
+            if buzzing {
+                title.push_str(" (buzzer)");
+            }
+            window.set_title(&title);
+        }
+
+        frames_since_flush += 1;
+        if frames_since_flush >= FRAMES_PER_SAVE_FLUSH && memory.take_save_dirty() {
+            flush_save(&mut save_file, &memory);
+            frames_since_flush = 0;
+        }
+
+        window
+            .update_with_buffer(memory.frame(), WIDTH, HEIGHT)
+            .unwrap();
+    }
+
+    if let Some(recording) = recording {
+        stop_recording(&mut memory, recording);
+    }
+
+    flush_save(&mut save_file, &memory);
+}
+
+/*
+ * Tilt in g from the mouse while its left button is held, otherwise from the tilt keys.
+ */
+fn tilt(window: &Window) -> (f32, f32) {
+    if window.get_mouse_down(MouseButton::Left) {
+        if let Some((x, y)) = window.get_mouse_pos(MouseMode::Clamp) {
+            let half_width = WIDTH as f32 / 2.0;
+            let half_height = HEIGHT as f32 / 2.0;
+            return (
+                (x - half_width) / half_width,
+                (y - half_height) / half_height,
+            );
+        }
+    }
+
+    TILT_KEYS
+        .iter()
+        .filter(|(key, _, _)| window.is_key_down(*key))
+        .fold((0.0, 0.0), |(x, y), (_, dx, dy)| (x + dx, y + dy))
+}
+
+fn flush_save(save_file: &mut SaveFile, memory: &Memory) {
+    if let Err(e) = save_file.flush(memory) {
+        eprintln!("Could not save to {}: {}", save_file.path().display(), e);
+    }
+}
+
+fn start_recording(
+    memory: &mut Memory,
+    audio: &Audio,
+    path: &std::path::Path,
+    split_channels: bool,
+) -> Option<Recording> {
+    let sample_rate = audio.nominal_sample_rate();
+    match Recording::start(path, sample_rate, split_channels) {
+        Ok(recording) => {
+            // Drop audio produced before the recording started.
+            memory.take_samples();
+            memory.set_sample_rate(sample_rate);
+            memory.set_channel_capture(recording.splits_channels());
+            eprintln!("Recording to {}", path.display());
+            Some(recording)
+        }
+        Err(e) => {
+            eprintln!("Could not record to {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn stop_recording(memory: &mut Memory, recording: Recording) {
+    memory.set_channel_capture(false);
+    let path = recording.path().to_path_buf();
+    match recording.finish() {
+        Ok(()) => eprintln!("Saved recording to {}", path.display()),
+        Err(e) => eprintln!("Could not finish {}: {}", path.display(), e),
     }
 }
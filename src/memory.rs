@@ -1,9 +1,10 @@
-use crate::io::{Joypad, Timers};
+use crate::apu::{Apu, DEFAULT_SAMPLE_RATE};
+use crate::cartridge::{self, header, ImageSource, Mbc};
+use crate::io::{Button, Interrupt, Joypad, Speed, Timers};
+use crate::ppu::{Ppu, Renderer, OAM_SIZE};
+use std::ops::Range;
 
 const ROM_BANK_0_START: u16 = 0x0000;
-const ROM_BANK_0_END: u16 = 0x3FFF;
-
-const ROM_BANK_N_START: u16 = 0x4000;
 const ROM_BANK_N_END: u16 = 0x7FFF;
 
 const VIDEO_RAM_START: u16 = 0x8000;
@@ -32,52 +33,223 @@ const HIGH_RAM_END: u16 = 0xFFFE;
 
 const INTERRUPT_ENABLE: u16 = 0xFFFF;
 
-const VIDEO_RAM_SIZE: usize = (VIDEO_RAM_END - VIDEO_RAM_START + 1) as usize;
-const EXTERNAL_RAM_SIZE: usize = (EXTERNAL_RAM_END - EXTERNAL_RAM_START + 1) as usize;
 const WORK_RAM_SIZE: usize = (WORK_RAM_END - WORK_RAM_START + 1) as usize;
-const OAM_SIZE: usize = (OAM_END - OAM_START + 1) as usize;
 const HIGH_RAM_SIZE: usize = (HIGH_RAM_END - HIGH_RAM_START + 1) as usize;
 
 pub struct Memory {
-    rom: Vec<u8>,
-    video_ram: [u8; VIDEO_RAM_SIZE],
-    ext_ram: [u8; EXTERNAL_RAM_SIZE],
+    cartridge: Box<dyn Mbc>,
+    // Rumble motor and buzzer states last reported to the frontend.
+    rumbling: bool,
+    buzzing: bool,
     work_ram: [u8; WORK_RAM_SIZE],
-    oam: [u8; OAM_SIZE],
     high_ram: [u8; HIGH_RAM_SIZE],
 
+    cgb: bool,
+    ppu: Ppu,
+    apu: Apu,
     joypad: Joypad,
     timers: Timers,
+    speed: Speed,
+    // Serial port registers. There is never a link partner, so transfers are only stored, never run.
+    serial_data: u8,
+    serial_control: u8,
     interrupt_flag: u8,
     interrupt_enable: u8,
 }
 
 impl Memory {
     pub(crate) fn new(rom: Vec<u8>) -> Self {
+        // CGB flag in the cartridge header
+        let cgb = rom.get(0x143).copied().is_some_and(header::is_cgb);
         Self {
-            rom,
-            video_ram: [0; VIDEO_RAM_SIZE],
-            ext_ram: [0; EXTERNAL_RAM_SIZE],
+            cartridge: cartridge::new(rom),
+            rumbling: false,
+            buzzing: false,
             work_ram: [0; WORK_RAM_SIZE],
-            oam: [0; OAM_SIZE],
             high_ram: [0; HIGH_RAM_SIZE],
+            cgb,
+            ppu: Ppu::new(),
+            apu: Apu::new(DEFAULT_SAMPLE_RATE),
             joypad: Joypad::new(),
             timers: Timers::new(),
+            speed: Speed::new(),
+            serial_data: 0,
+            serial_control: 0,
             interrupt_flag: 0,
             interrupt_enable: 0,
         }
     }
 
+    /*
+     * Advance the memory-mapped peripherals by the given number of CPU T-cycles.
+     * Returns the elapsed time in single-speed T-cycles; in CGB double-speed mode the CPU and timers run twice as fast
+     * relative to everything else.
+     */
+    pub(crate) fn tick(&mut self, t_cycles: u8) -> u8 {
+        let divider = self.timers.get_divider();
+        if self.timers.tick(t_cycles as usize) {
+            self.request_interrupt(Interrupt::Timer);
+        }
+        if self.div_apu_edge(divider, self.timers.get_divider()) {
+            self.apu.clock_frame_sequencer();
+        }
+        self.cartridge.tick(t_cycles);
+
+        let dots = if self.speed.is_double_speed() {
+            t_cycles / 2
+        } else {
+            t_cycles
+        };
+        self.interrupt_flag |= self.ppu.tick(dots);
+        self.apu.tick(dots);
+        dots
+    }
+
+    /*
+     * Whether DIV changing from before to after clocks the APU frame sequencer.
+     */
+    fn div_apu_edge(&self, before: u8, after: u8) -> bool {
+        let bit = if self.speed.is_double_speed() {
+            0b100000
+        } else {
+            0b10000
+        };
+        before & bit != 0 && after & bit == 0
+    }
+
+    pub(crate) fn frame(&self) -> &[u32] {
+        self.ppu.frame()
+    }
+
+    pub(crate) fn set_renderer(&mut self, renderer: Renderer) {
+        self.ppu.set_renderer(renderer);
+    }
+
+    pub(crate) fn set_button(&mut self, button: Button, pressed: bool) {
+        if self.joypad.set_pressed(button, pressed) {
+            self.request_interrupt(Interrupt::Joypad);
+        }
+    }
+
+    pub(crate) fn joypad_line_low(&self) -> bool {
+        self.joypad.any_line_low()
+    }
+
+    pub(crate) fn reset_div(&mut self) {
+        let divider = self.timers.get_divider();
+        self.timers.reset_div();
+        if self.div_apu_edge(divider, 0) {
+            self.apu.clock_frame_sequencer();
+        }
+    }
+
+    /*
+     * Battery-backed cartridge memory, if the cartridge has any.
+     */
+    pub(crate) fn save_data(&self) -> Option<Vec<u8>> {
+        self.cartridge.save()
+    }
+
+    pub(crate) fn load_save_data(&mut self, data: &[u8]) {
+        self.cartridge.load(data);
+    }
+
+    /*
+     * Where in the save data the clock footer's timestamp is, if the cartridge has one.
+     */
+    pub(crate) fn save_timestamp(&self) -> Option<Range<usize>> {
+        self.cartridge.save_timestamp()
+    }
+
+    /*
+     * Whether battery-backed memory has changed since the last call.
+     */
+    pub(crate) fn take_save_dirty(&mut self) -> bool {
+        self.cartridge.take_dirty()
+    }
+
+    /*
+     * The new rumble motor state if it changed since the last call.
+     */
+    pub(crate) fn rumble_event(&mut self) -> Option<bool> {
+        let rumbling = self.cartridge.rumbling();
+        if rumbling == self.rumbling {
+            return None;
+        }
+        self.rumbling = rumbling;
+        Some(rumbling)
+    }
+
+    /*
+     * The new buzzer state if it changed since the last call.
+     */
+    pub(crate) fn buzzer_event(&mut self) -> Option<bool> {
+        let buzzing = self.cartridge.buzzing();
+        if buzzing == self.buzzing {
+            return None;
+        }
+        self.buzzing = buzzing;
+        Some(buzzing)
+    }
+
+    pub(crate) fn set_tilt(&mut self, x: f32, y: f32) {
+        self.cartridge.set_tilt(x, y);
+    }
+
+    pub(crate) fn set_image_source(&mut self, source: Box<dyn ImageSource>) {
+        self.cartridge.set_image_source(source);
+    }
+
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.apu.set_sample_rate(sample_rate);
+    }
+
+    pub(crate) fn take_samples(&mut self) -> Vec<f32> {
+        self.apu.take_samples()
+    }
+
+    pub(crate) fn set_channel_capture(&mut self, enabled: bool) {
+        self.apu.set_channel_capture(enabled);
+    }
+
+    pub(crate) fn take_channel_samples(&mut self) -> [Vec<f32>; 4] {
+        self.apu.take_channel_samples()
+    }
+
+    /*
+     * Whether a STOP should perform a CGB speed switch rather than enter very low power mode.
+     */
+    pub(crate) fn speed_switch_armed(&self) -> bool {
+        self.cgb && self.speed.is_switch_armed()
+    }
+
+    pub(crate) fn switch_speed(&mut self) {
+        self.speed.switch();
+    }
+
+    pub(crate) fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupt_flag |= interrupt.bit();
+    }
+
+    pub(crate) fn acknowledge_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupt_flag &= !interrupt.bit();
+    }
+
+    /*
+     * Interrupts that are both requested (IF) and enabled (IE).
+     */
+    pub(crate) fn pending_interrupts(&self) -> u8 {
+        self.interrupt_flag & self.interrupt_enable & 0x1F
+    }
+
     pub(crate) fn read(&self, address: u16) -> u8 {
         match address {
-            ROM_BANK_0_START..=ROM_BANK_N_END => self.rom[address as usize],
-            VIDEO_RAM_START..=VIDEO_RAM_END => self.video_ram[(address - VIDEO_RAM_START) as usize],
-            EXTERNAL_RAM_START..=EXTERNAL_RAM_END => {
-                self.ext_ram[(address - EXTERNAL_RAM_START) as usize]
-            }
+            ROM_BANK_0_START..=ROM_BANK_N_END => self.cartridge.read_rom(address),
+            VIDEO_RAM_START..=VIDEO_RAM_END => self.ppu.read_video_ram(address - VIDEO_RAM_START),
+            EXTERNAL_RAM_START..=EXTERNAL_RAM_END => self.cartridge.read_ram(address),
             WORK_RAM_START..=WORK_RAM_END => self.work_ram[(address - WORK_RAM_START) as usize],
             ECHO_RAM_START..=ECHO_RAM_END => self.work_ram[(address - ECHO_RAM_START) as usize],
-            OAM_START..=OAM_END => self.oam[(address - OAM_START) as usize],
+            OAM_START..=OAM_END => self.ppu.read_oam(address - OAM_START),
             UNUSABLE_START..=UNUSABLE_END => 0x00, // TODO
             IO_START..=IO_END => self.read_io(address),
             HIGH_RAM_START..=HIGH_RAM_END => self.high_ram[(address - HIGH_RAM_START) as usize],
@@ -87,20 +259,18 @@ impl Memory {
 
     pub(crate) fn write(&mut self, address: u16, byte: u8) {
         match address {
-            ROM_BANK_0_START..=ROM_BANK_N_END => self.rom[address as usize] = byte,
+            ROM_BANK_0_START..=ROM_BANK_N_END => self.cartridge.write_rom(address, byte),
             VIDEO_RAM_START..=VIDEO_RAM_END => {
-                self.video_ram[(address - VIDEO_RAM_START) as usize] = byte
-            }
-            EXTERNAL_RAM_START..=EXTERNAL_RAM_END => {
-                self.ext_ram[(address - EXTERNAL_RAM_START) as usize] = byte
+                self.ppu.write_video_ram(address - VIDEO_RAM_START, byte)
             }
+            EXTERNAL_RAM_START..=EXTERNAL_RAM_END => self.cartridge.write_ram(address, byte),
             WORK_RAM_START..=WORK_RAM_END => {
                 self.work_ram[(address - WORK_RAM_START) as usize] = byte
             }
             ECHO_RAM_START..=ECHO_RAM_END => {
                 self.work_ram[(address - ECHO_RAM_START) as usize] = byte
             }
-            OAM_START..=OAM_END => self.oam[(address - OAM_START) as usize] = byte,
+            OAM_START..=OAM_END => self.ppu.write_oam(address - OAM_START, byte),
             UNUSABLE_START..=UNUSABLE_END => {} // TODO
             IO_START..=IO_END => self.write_io(address, byte),
             HIGH_RAM_START..=HIGH_RAM_END => {
@@ -114,35 +284,60 @@ impl Memory {
         match address {
             0x0000..0xFF00 | 0xFF80..=0xFFFF => unreachable!(),
             0xFF00 => self.joypad.get(),
-            0xFF01 => todo!(),
-            0xFF02 => todo!(),
-            0xFF03 => unimplemented!(),
+            0xFF01 => self.serial_data,
+            // Only the transfer start and clock select bits exist.
+            0xFF02 => 0x7E | self.serial_control,
+            0xFF03 => 0xFF,
             0xFF04 => self.timers.get_divider(),
             0xFF05 => self.timers.get_counter(),
             0xFF06 => self.timers.get_tma(),
             0xFF07 => self.timers.get_tac(),
-            0xFF08..=0xFF0E => unimplemented!(),
+            0xFF08..=0xFF0E => 0xFF,
             0xFF0F => self.interrupt_flag,
-            0xFF10..=0xFF3F => todo!(), // Sound
-            0xFF4F..=0xFF77 => 0x0,     // CGB only
+            0xFF10..=0xFF3F => self.apu.read(address),
+            0xFF40..=0xFF4B => self.ppu.read(address),
+            0xFF4D if self.cgb => self.speed.get(),
+            0xFF4C..=0xFF4E => 0xFF,
+            0xFF4F..=0xFF77 => 0x0, // CGB only
+            0xFF78..=0xFF7F => 0xFF,
         }
     }
 
-    fn write_io(&self, address: u16, byte: u8) {
+    fn write_io(&mut self, address: u16, byte: u8) {
         match address {
             0x0000..0xFF00 | 0xFF80..=0xFFFF => unreachable!(),
             0xFF00 => self.joypad.set(byte),
-            0xFF01 => todo!(),
-            0xFF02 => todo!(),
-            0xFF03 => unimplemented!(),
-            0xFF04 => self.timers.reset_div(),
+            0xFF01 => self.serial_data = byte,
+            0xFF02 => self.serial_control = byte & 0x81,
+            0xFF03 => {}
+            0xFF04 => self.reset_div(),
             0xFF05 => self.timers.set_counter(byte),
             0xFF06 => self.timers.set_tma(byte),
             0xFF07 => self.timers.set_tac(byte),
-            0xFF08..=0xFF0E => unimplemented!(),
+            0xFF08..=0xFF0E => {}
             0xFF0F => self.interrupt_flag = byte,
-            0xFF10..=0xFF3F => todo!(), // Sound
-            0xFF4F..=0xFF77 => {}       // CGB only
+            0xFF10..=0xFF3F => self.apu.write(address, byte),
+            0xFF46 => {
+                self.ppu.write(address, byte);
+                self.oam_dma(byte);
+            }
+            0xFF40..=0xFF4B => self.ppu.write(address, byte),
+            0xFF4D if self.cgb => self.speed.set(byte),
+            0xFF4C..=0xFF4E => {}
+            0xFF4F..=0xFF77 => {} // CGB only
+            0xFF78..=0xFF7F => {}
         };
     }
+
+    /*
+     * Copy 160 bytes from $XX00 into OAM, where XX is the byte written to DMA.
+     * The transfer happens all at once rather than over 160 M-cycles.
+     */
+    fn oam_dma(&mut self, source: u8) {
+        let start = (source as u16) << 8;
+        for offset in 0..OAM_SIZE as u16 {
+            let byte = self.read(start + offset);
+            self.ppu.write_oam(offset, byte);
+        }
+    }
 }
@@ -0,0 +1,83 @@
+use super::{check_footer, check_size, check_target, PatchError, Reader};
+
+pub(super) const MAGIC: &[u8] = b"BPS1";
+
+/*
+ * Beat Patching System: the target is built front to back from runs copied from the source at the same offset, literal
+ * bytes from the patch, or runs copied from anywhere in the source or the target written so far.
+ */
+pub(super) fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let target_checksum = check_footer(rom, patch)?;
+    let mut reader = Reader::new(&patch[..patch.len() - 12], MAGIC.len());
+    let _source_size = reader.number()?;
+    let target_size = check_size(reader.number()?)?;
+    let metadata_size = reader.number()?;
+    reader.bytes(metadata_size)?;
+
+    let mut target = Vec::with_capacity(target_size);
+    let mut source_offset = 0;
+    let mut target_offset = 0;
+    while reader.remaining() > 0 {
+        let action = reader.number()?;
+        let length = (action >> 2) + 1;
+        // No action may write past the size the patch declared.
+        if length > target_size - target.len() {
+            return Err(PatchError::OutOfRange {
+                offset: target.len(),
+            });
+        }
+        match action & 3 {
+            // Source read
+            0 => {
+                let start = target.len();
+                let run = rom
+                    .get(start..start + length)
+                    .ok_or(PatchError::OutOfRange { offset: start })?;
+                target.extend_from_slice(run);
+            }
+            // Target read
+            1 => target.extend_from_slice(reader.bytes(length)?),
+            // Source copy
+            2 => {
+                source_offset = relative(source_offset, reader.number()?)?;
+                let run = rom
+                    .get(source_offset..source_offset.saturating_add(length))
+                    .ok_or(PatchError::OutOfRange {
+                        offset: source_offset,
+                    })?;
+                target.extend_from_slice(run);
+                source_offset += length;
+            }
+            // Target copy, byte by byte since the run may overlap what it is writing.
+            _ => {
+                target_offset = relative(target_offset, reader.number()?)?;
+                for _ in 0..length {
+                    let byte = *target.get(target_offset).ok_or(PatchError::OutOfRange {
+                        offset: target_offset,
+                    })?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(PatchError::Truncated);
+    }
+    check_target(&target, target_checksum)?;
+    Ok(target)
+}
+
+/*
+ * Move a copy offset by a signed distance, stored as a magnitude with the sign in the low bit.
+ */
+fn relative(offset: usize, distance: usize) -> Result<usize, PatchError> {
+    let magnitude = distance >> 1;
+    if distance & 1 != 0 {
+        offset.checked_sub(magnitude)
+    } else {
+        offset.checked_add(magnitude)
+    }
+    .ok_or(PatchError::OutOfRange { offset })
+}
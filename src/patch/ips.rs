@@ -0,0 +1,48 @@
+use super::{check_size, PatchError, Reader};
+
+pub(super) const MAGIC: &[u8] = b"PATCH";
+
+// An offset spelling "EOF" ends the records.
+const END: [u8; 3] = *b"EOF";
+
+/*
+ * International Patching System: records of bytes to write at 24-bit offsets, or a run of one byte when the length is
+ * zero. Writes past the end grow the ROM, and an optional length after the end marker truncates it. IPS carries no
+ * checksums, so nothing can be validated.
+ */
+pub(super) fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut target = rom.to_vec();
+    let mut reader = Reader::new(patch, MAGIC.len());
+
+    loop {
+        let offset = reader.bytes(3)?;
+        if offset == END {
+            break;
+        }
+        let offset = be(offset);
+        let length = be(reader.bytes(2)?);
+        let data = if length == 0 {
+            let length = be(reader.bytes(2)?);
+            vec![reader.byte()?; length]
+        } else {
+            reader.bytes(length)?.to_vec()
+        };
+
+        let end = offset + data.len();
+        if target.len() < end {
+            target.resize(check_size(end)?, 0);
+        }
+        target[offset..end].copy_from_slice(&data);
+    }
+
+    if reader.remaining() >= 3 {
+        target.truncate(be(reader.bytes(3)?));
+    }
+    Ok(target)
+}
+
+fn be(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .fold(0, |value, &byte| (value << 8) | byte as usize)
+}
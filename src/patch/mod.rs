@@ -0,0 +1,412 @@
+mod bps;
+mod ips;
+mod ups;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Patch formats by the extension they are looked for under, next to the ROM.
+const EXTENSIONS: [&str; 3] = ["ips", "ups", "bps"];
+
+// MBC5 tops out at 8 MiB, so a patch asking for a bigger ROM is corrupt rather than something to allocate for.
+const MAX_ROM_SIZE: usize = 8 * 1024 * 1024;
+
+/*
+ * Why a patch couldn't be applied. Checksum mismatches on the source mean the patch was made for a different ROM (or
+ * revision of it).
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum PatchError {
+    UnknownFormat,
+    Truncated,
+    // A copy reaching outside the source or target.
+    OutOfRange { offset: usize },
+    TooLarge { size: usize },
+    PatchChecksum { expected: u32, actual: u32 },
+    SourceChecksum { expected: u32, actual: u32 },
+    TargetChecksum { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::UnknownFormat => write!(f, "not an IPS, UPS or BPS patch"),
+            PatchError::Truncated => write!(f, "patch ends unexpectedly"),
+            PatchError::OutOfRange { offset } => {
+                write!(f, "patch reaches outside the ROM at ${:X}", offset)
+            }
+            PatchError::TooLarge { size } => write!(
+                f,
+                "patched ROM would be {} bytes, more than the {} a cartridge can hold",
+                size, MAX_ROM_SIZE
+            ),
+            PatchError::PatchChecksum { expected, actual } => write!(
+                f,
+                "patch is corrupt: CRC32 is {:08x}, expected {:08x}",
+                actual, expected
+            ),
+            PatchError::SourceChecksum { expected, actual } => write!(
+                f,
+                "patch is for a different ROM: CRC32 is {:08x}, expected {:08x}",
+                actual, expected
+            ),
+            PatchError::TargetChecksum { expected, actual } => write!(
+                f,
+                "patched ROM's CRC32 is {:08x}, expected {:08x}",
+                actual, expected
+            ),
+        }
+    }
+}
+
+/*
+ * Apply a patch to a copy of the ROM, detecting its format from its contents. The ROM itself is left untouched.
+ */
+pub(crate) fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.starts_with(ips::MAGIC) {
+        ips::apply(rom, patch)
+    } else if patch.starts_with(ups::MAGIC) {
+        ups::apply(rom, patch)
+    } else if patch.starts_with(bps::MAGIC) {
+        bps::apply(rom, patch)
+    } else {
+        Err(PatchError::UnknownFormat)
+    }
+}
+
+/*
+ * Patches sharing the ROM's name, e.g. `game.ips` for `game.gb`, applied in the order IPS, UPS, BPS.
+ */
+pub(crate) fn discover(rom_path: &Path) -> Vec<PathBuf> {
+    EXTENSIONS
+        .iter()
+        .map(|extension| rom_path.with_extension(extension))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/*
+ * Reads a patch front to back, failing with Truncated when it runs out.
+ */
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], position: usize) -> Self {
+        Self { data, position }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.position)
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], PatchError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .ok_or(PatchError::Truncated)?;
+        let bytes = self
+            .data
+            .get(self.position..end)
+            .ok_or(PatchError::Truncated)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, PatchError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /*
+     * The variable-length number used by UPS and BPS: seven bits per byte, least significant first, with the top bit
+     * marking the last byte. Each continuation also adds one so every number has exactly one encoding.
+     */
+    fn number(&mut self) -> Result<usize, PatchError> {
+        let mut number = 0usize;
+        let mut shift = 1usize;
+        loop {
+            let byte = self.byte()?;
+            number = (byte as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|value| number.checked_add(value))
+                .ok_or(PatchError::Truncated)?;
+            if byte & 0x80 != 0 {
+                return Ok(number);
+            }
+            shift = shift.checked_shl(7).ok_or(PatchError::Truncated)?;
+            number = number.checked_add(shift).ok_or(PatchError::Truncated)?;
+        }
+    }
+}
+
+/*
+ * Check the footer UPS and BPS share: CRC32s of the source, the target and the patch up to its own checksum. The patch
+ * and source are checked before anything is applied; the target's CRC32 is returned to check afterwards.
+ */
+fn check_footer(rom: &[u8], patch: &[u8]) -> Result<u32, PatchError> {
+    if patch.len() < 12 {
+        return Err(PatchError::Truncated);
+    }
+    let footer = &patch[patch.len() - 12..];
+    let crc =
+        |index: usize| u32::from_le_bytes(footer[index * 4..index * 4 + 4].try_into().unwrap());
+
+    let expected = crc(2);
+    let actual = crc32fast::hash(&patch[..patch.len() - 4]);
+    if actual != expected {
+        return Err(PatchError::PatchChecksum { expected, actual });
+    }
+    let expected = crc(0);
+    let actual = crc32fast::hash(rom);
+    if actual != expected {
+        return Err(PatchError::SourceChecksum { expected, actual });
+    }
+    Ok(crc(1))
+}
+
+/*
+ * Reject a target size no cartridge could have before allocating for it.
+ */
+fn check_size(size: usize) -> Result<usize, PatchError> {
+    if size > MAX_ROM_SIZE {
+        return Err(PatchError::TooLarge { size });
+    }
+    Ok(size)
+}
+
+fn check_target(target: &[u8], expected: u32) -> Result<(), PatchError> {
+    let actual = crc32fast::hash(target);
+    if actual != expected {
+        return Err(PatchError::TargetChecksum { expected, actual });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     * A number in the UPS/BPS encoding, the inverse of `Reader::number`.
+     */
+    fn number(mut value: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let low = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(0x80 | low);
+                return bytes;
+            }
+            bytes.push(low);
+            value -= 1;
+        }
+    }
+
+    /*
+     * A UPS or BPS patch: magic, body, then the checksums of the source, the target and the patch itself.
+     */
+    fn with_footer(magic: &[u8], body: &[u8], source: &[u8], target: &[u8]) -> Vec<u8> {
+        let mut patch = [magic, body].concat();
+        patch.extend(crc32fast::hash(source).to_le_bytes());
+        patch.extend(crc32fast::hash(target).to_le_bytes());
+        patch.extend(crc32fast::hash(&patch).to_le_bytes());
+        patch
+    }
+
+    fn ups(source: &[u8], target: &[u8], hunks: &[u8]) -> Vec<u8> {
+        let body = [number(source.len()), number(target.len()), hunks.to_vec()].concat();
+        with_footer(ups::MAGIC, &body, source, target)
+    }
+
+    // BPS actions, as (kind, length) packed into one number.
+    fn action(kind: usize, length: usize) -> Vec<u8> {
+        number(((length - 1) << 2) | kind)
+    }
+
+    // A signed BPS copy distance: the magnitude with the sign in the low bit.
+    fn distance(distance: isize) -> Vec<u8> {
+        number((distance.unsigned_abs() << 1) | (distance < 0) as usize)
+    }
+
+    fn bps(source: &[u8], target: &[u8], actions: &[Vec<u8>]) -> Vec<u8> {
+        let body = [
+            number(source.len()),
+            number(target.len()),
+            number(0),
+            actions.concat(),
+        ]
+        .concat();
+        with_footer(bps::MAGIC, &body, source, target)
+    }
+
+    #[test]
+    fn number_round_trips() {
+        for value in [0, 1, 0x7F, 0x80, 0x3FFF, 0x4000, 0x4080, MAX_ROM_SIZE] {
+            assert_eq!(Reader::new(&number(value), 0).number(), Ok(value));
+        }
+    }
+
+    #[test]
+    fn ips_records_and_runs_grow_the_rom() {
+        let rom = [0; 8];
+        let patch = [
+            &b"PATCH"[..],
+            // Two bytes at 1.
+            &[0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB],
+            // A run of three 0xCC at 4.
+            &[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x03, 0xCC],
+            // One byte at 10, past the end.
+            &[0x00, 0x00, 0x0A, 0x00, 0x01, 0xDD],
+            b"EOF",
+        ]
+        .concat();
+        assert_eq!(
+            apply(&rom, &patch),
+            Ok(vec![0, 0xAA, 0xBB, 0, 0xCC, 0xCC, 0xCC, 0, 0, 0, 0xDD])
+        );
+    }
+
+    #[test]
+    fn ips_truncates_after_the_end_marker() {
+        let rom = [1, 2, 3, 4, 5, 6];
+        let patch = [
+            &b"PATCH"[..],
+            &[0x00, 0x00, 0x00, 0x00, 0x01, 0x09],
+            b"EOF",
+            &[0x00, 0x00, 0x03],
+        ]
+        .concat();
+        assert_eq!(apply(&rom, &patch), Ok(vec![9, 2, 3]));
+    }
+
+    #[test]
+    fn ups_xors_past_the_end_of_the_source() {
+        let source = [0x01, 0x02, 0x03];
+        let target = [0x01, 0x22, 0x03, 0x44, 0x55];
+        let hunks = [
+            number(1),
+            vec![0x02 ^ 0x22, 0x00],
+            // The end of the last hunk skips offset 2, so this starts at 3, beyond the source.
+            number(0),
+            vec![0x44, 0x55, 0x00],
+        ]
+        .concat();
+        assert_eq!(
+            apply(&source, &ups(&source, &target, &hunks)),
+            Ok(target.to_vec())
+        );
+    }
+
+    #[test]
+    fn bps_copies_with_relative_offsets() {
+        let source = [10, 20, 30, 40];
+        let target = [10, 30, 40, 10, 20, 10, 20, 10, 20, 30, 99];
+        let actions = [
+            // Source read: [10]
+            action(0, 1),
+            // Source copy forward to 2: [30, 40]
+            [action(2, 2), distance(2)].concat(),
+            // Source copy back from 4 to 0: [10, 20]
+            [action(2, 2), distance(-4)].concat(),
+            // Target copy from 3, overlapping what it writes: [10, 20, 10, 20]
+            [action(3, 4), distance(3)].concat(),
+            // Target copy back from 7 to 1: [30]
+            [action(3, 1), distance(-6)].concat(),
+            // Target read: [99]
+            [action(1, 1), vec![99]].concat(),
+        ];
+        assert_eq!(
+            apply(&source, &bps(&source, &target, &actions)),
+            Ok(target.to_vec())
+        );
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert_eq!(apply(&[0; 4], b"NOPE"), Err(PatchError::UnknownFormat));
+    }
+
+    #[test]
+    fn truncated() {
+        let patch = [&b"PATCH"[..], &[0x00, 0x00, 0x01, 0x00]].concat();
+        assert_eq!(apply(&[0; 4], &patch), Err(PatchError::Truncated));
+        assert_eq!(
+            Reader::new(&[0; 4], 1).bytes(usize::MAX),
+            Err(PatchError::Truncated)
+        );
+    }
+
+    #[test]
+    fn out_of_range() {
+        let source = [1, 2, 3, 4];
+        let patch = bps(&source, &[1], &[[action(2, 1), distance(-1)].concat()]);
+        assert_eq!(
+            apply(&source, &patch),
+            Err(PatchError::OutOfRange { offset: 0 })
+        );
+
+        // A run longer than the declared target.
+        let patch = bps(&source, &[1], &[action(0, 2)]);
+        assert_eq!(
+            apply(&source, &patch),
+            Err(PatchError::OutOfRange { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn too_large() {
+        let source = [0; 4];
+        let size = MAX_ROM_SIZE + 1;
+        let body = [number(source.len()), number(size)].concat();
+        let patch = with_footer(ups::MAGIC, &body, &source, &[]);
+        assert_eq!(apply(&source, &patch), Err(PatchError::TooLarge { size }));
+
+        let patch = [&b"PATCH"[..], &[0xFF, 0xFF, 0xFF, 0x00, 0x01, 0x00], b"EOF"].concat();
+        assert_eq!(
+            apply(&source, &patch),
+            Err(PatchError::TooLarge { size: 0x1000000 })
+        );
+    }
+
+    #[test]
+    fn patch_checksum() {
+        let source = [1, 2, 3];
+        let mut patch = ups(&source, &source, &[]);
+        let expected = u32::from_le_bytes(patch[patch.len() - 4..].try_into().unwrap());
+        patch[4] ^= 1;
+        let actual = crc32fast::hash(&patch[..patch.len() - 4]);
+        assert_eq!(
+            apply(&source, &patch),
+            Err(PatchError::PatchChecksum { expected, actual })
+        );
+    }
+
+    #[test]
+    fn source_checksum() {
+        let patch = ups(&[1, 2, 3], &[1, 2, 3], &[]);
+        assert_eq!(
+            apply(&[4, 5, 6], &patch),
+            Err(PatchError::SourceChecksum {
+                expected: crc32fast::hash(&[1, 2, 3]),
+                actual: crc32fast::hash(&[4, 5, 6]),
+            })
+        );
+    }
+
+    #[test]
+    fn target_checksum() {
+        let source = [1, 2, 3];
+        let hunks = [number(0), vec![0x01, 0x00]].concat();
+        let patch = ups(&source, &source, &hunks);
+        assert_eq!(
+            apply(&source, &patch),
+            Err(PatchError::TargetChecksum {
+                expected: crc32fast::hash(&source),
+                actual: crc32fast::hash(&[0, 2, 3]),
+            })
+        );
+    }
+}
@@ -0,0 +1,41 @@
+use super::{check_footer, check_size, check_target, PatchError, Reader};
+
+pub(super) const MAGIC: &[u8] = b"UPS1";
+
+/*
+ * Universal Patching System: hunks of bytes XORed into the ROM, each after a run of unchanged bytes. A zero byte ends a
+ * hunk (and skips that byte, since XORing with zero changes nothing).
+ */
+pub(super) fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let target_checksum = check_footer(rom, patch)?;
+    let mut reader = Reader::new(&patch[..patch.len() - 12], MAGIC.len());
+    let _source_size = reader.number()?;
+    let target_size = check_size(reader.number()?)?;
+
+    // Bytes beyond the end of the source XOR against zero.
+    let mut target = rom.to_vec();
+    target.resize(target_size, 0);
+
+    let mut offset = 0usize;
+    while reader.remaining() > 0 {
+        offset = offset
+            .checked_add(reader.number()?)
+            .filter(|&offset| offset <= target.len())
+            .ok_or(PatchError::OutOfRange { offset })?;
+        loop {
+            let byte = reader.byte()?;
+            if byte != 0 {
+                *target
+                    .get_mut(offset)
+                    .ok_or(PatchError::OutOfRange { offset })? ^= byte;
+            }
+            offset += 1;
+            if byte == 0 {
+                break;
+            }
+        }
+    }
+
+    check_target(&target, target_checksum)?;
+    Ok(target)
+}
@@ -0,0 +1,317 @@
+use std::collections::VecDeque;
+
+use super::*;
+
+// Dots taken by each of the tile number, low data and high data fetches.
+const FETCH_STEP_DOTS: u8 = 2;
+// Dots taken to fetch a sprite once the background fetcher has finished its current tile.
+const SPRITE_FETCH_DOTS: u8 = 6;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FetchStep {
+    Tile,
+    DataLow,
+    DataHigh,
+    Push,
+}
+
+#[derive(Clone, Copy)]
+struct ObjPixel {
+    color: u8,
+    palette: u8,
+    bg_priority: bool,
+}
+
+const TRANSPARENT: ObjPixel = ObjPixel {
+    color: 0,
+    palette: 0,
+    bg_priority: false,
+};
+
+/*
+ * Per-line state of the background/window fetcher, sprite fetcher and their FIFOs.
+ */
+pub(super) struct PixelFifo {
+    bg: VecDeque<u8>,
+    obj: VecDeque<ObjPixel>,
+
+    step: FetchStep,
+    step_dots: u8,
+    fetch_x: u8,
+    tile_address: usize,
+    tile_row: u8,
+    data_low: u8,
+    data_high: u8,
+    first_fetch: bool,
+    window: bool,
+
+    sprites: Vec<Sprite>,
+    sprite_fetch: Option<(Sprite, u8)>,
+
+    discard: u8,
+    lcd_x: u8,
+}
+
+impl PixelFifo {
+    pub(super) fn new() -> Self {
+        Self {
+            bg: VecDeque::with_capacity(16),
+            obj: VecDeque::with_capacity(8),
+            step: FetchStep::Tile,
+            step_dots: 0,
+            fetch_x: 0,
+            tile_address: 0,
+            tile_row: 0,
+            data_low: 0,
+            data_high: 0,
+            first_fetch: true,
+            window: false,
+            sprites: Vec::new(),
+            sprite_fetch: None,
+            discard: 0,
+            lcd_x: 0,
+        }
+    }
+
+    pub(super) fn line_done(&self) -> bool {
+        self.lcd_x as usize >= WIDTH
+    }
+
+    fn restart_fetcher(&mut self) {
+        self.step = FetchStep::Tile;
+        self.step_dots = 0;
+        self.fetch_x = 0;
+    }
+}
+
+impl Ppu {
+    /*
+     * Reset the FIFOs and fetchers for a new line, and select the sprites it will draw.
+     */
+    pub(super) fn start_fifo_line(&mut self) {
+        let mut sprites = self.scan_oam();
+        // Sprites are fetched as the LCD reaches them, so keep them in X order (stable, so ties stay in OAM order).
+        sprites.sort_by_key(|sprite| sprite.x);
+
+        let fifo = &mut self.fifo;
+        fifo.bg.clear();
+        fifo.obj.clear();
+        fifo.restart_fetcher();
+        fifo.first_fetch = true;
+        fifo.window = false;
+        fifo.sprites = sprites;
+        fifo.sprite_fetch = None;
+        // The fine scroll is applied by dropping the first SCX % 8 pixels of the line.
+        fifo.discard = self.scroll_x % 8;
+        fifo.lcd_x = 0;
+    }
+
+    pub(super) fn end_fifo_line(&mut self) {
+        if self.fifo.window {
+            self.window_line += 1;
+        }
+    }
+
+    /*
+     * Advance mode 3 by one dot: step the fetchers, then shift at most one pixel out to the LCD.
+     */
+    pub(super) fn tick_fifo(&mut self) {
+        if self.fifo.line_done() {
+            return;
+        }
+
+        self.start_window();
+        self.tick_bg_fetcher();
+
+        if self.tick_sprite_fetcher() {
+            return;
+        }
+
+        let Some(bg_color) = self.fifo.bg.pop_front() else {
+            return;
+        };
+        let obj = self.fifo.obj.pop_front().unwrap_or(TRANSPARENT);
+
+        if self.fifo.discard > 0 {
+            self.fifo.discard -= 1;
+            return;
+        }
+
+        let bg_color = if self.lcd_control & LCDC_BG_ENABLE != 0 {
+            bg_color
+        } else {
+            0
+        };
+        let obj_visible = obj.color != 0
+            && self.lcd_control & LCDC_OBJ_ENABLE != 0
+            && !(obj.bg_priority && bg_color != 0);
+        let pixel = if obj_visible {
+            shade(obj.palette, obj.color)
+        } else {
+            shade(self.bg_palette, bg_color)
+        };
+
+        self.frame[self.ly as usize * WIDTH + self.fifo.lcd_x as usize] = pixel;
+        self.fifo.lcd_x += 1;
+    }
+
+    /*
+     * Switch the fetcher over to the window once the LCD reaches WX on a line at or below WY.
+     * The background FIFO is emptied, so the restarted fetch stalls output for a tile's worth of dots.
+     */
+    fn start_window(&mut self) {
+        let fifo = &mut self.fifo;
+        if fifo.window
+            || fifo.discard > 0
+            || self.lcd_control & LCDC_WINDOW_ENABLE == 0
+            || !self.window_y_triggered
+            || self.window_x > 166
+            || (fifo.lcd_x as usize + 7) < self.window_x as usize
+        {
+            return;
+        }
+
+        fifo.window = true;
+        fifo.bg.clear();
+        fifo.restart_fetcher();
+        // Pixels of a window starting left of the screen edge are dropped.
+        fifo.discard = 7u8.saturating_sub(self.window_x);
+    }
+
+    fn tick_bg_fetcher(&mut self) {
+        let step = self.fifo.step;
+        if step != FetchStep::Push {
+            self.fifo.step_dots += 1;
+            if self.fifo.step_dots < FETCH_STEP_DOTS {
+                return;
+            }
+            self.fifo.step_dots = 0;
+        }
+
+        match step {
+            FetchStep::Tile => {
+                let (index, row) = self.fetch_tile_index();
+                self.fifo.tile_address = self.bg_tile_address(index);
+                self.fifo.tile_row = row;
+                self.fifo.step = FetchStep::DataLow;
+            }
+            FetchStep::DataLow => {
+                let address = self.fifo.tile_address + self.fifo.tile_row as usize * 2;
+                self.fifo.data_low = self.video_ram[address];
+                self.fifo.step = FetchStep::DataHigh;
+            }
+            FetchStep::DataHigh => {
+                let address = self.fifo.tile_address + self.fifo.tile_row as usize * 2 + 1;
+                self.fifo.data_high = self.video_ram[address];
+                if self.fifo.first_fetch {
+                    // The first fetch of every line is thrown away and repeated.
+                    self.fifo.first_fetch = false;
+                    self.fifo.restart_fetcher();
+                } else {
+                    self.fifo.step = FetchStep::Push;
+                }
+            }
+            FetchStep::Push => {
+                if !self.fifo.bg.is_empty() {
+                    return;
+                }
+                let fifo = &mut self.fifo;
+                for bit in (0..8).rev() {
+                    let color = (((fifo.data_high >> bit) & 1) << 1) | ((fifo.data_low >> bit) & 1);
+                    fifo.bg.push_back(color);
+                }
+                fifo.fetch_x = fifo.fetch_x.wrapping_add(1);
+                fifo.step = FetchStep::Tile;
+            }
+        }
+    }
+
+    /*
+     * Tile number and row within it for the fetcher's current position, read with the registers as they are now.
+     */
+    fn fetch_tile_index(&self) -> (u8, u8) {
+        let (high_map, x, y) = if self.fifo.window {
+            (
+                self.lcd_control & LCDC_WINDOW_TILE_MAP != 0,
+                self.fifo.fetch_x,
+                self.window_line,
+            )
+        } else {
+            (
+                self.lcd_control & LCDC_BG_TILE_MAP != 0,
+                (self.scroll_x / 8).wrapping_add(self.fifo.fetch_x) & 31,
+                self.scroll_y.wrapping_add(self.ly),
+            )
+        };
+        let map = if high_map { 0x1C00 } else { 0x1800 };
+        let index = self.video_ram[map + (y as usize / 8) * 32 + (x as usize & 31)];
+        (index, y % 8)
+    }
+
+    /*
+     * Start or continue fetching the next sprite the LCD has reached. Pixel output is paused while this returns true.
+     */
+    fn tick_sprite_fetcher(&mut self) -> bool {
+        if self.fifo.sprite_fetch.is_none() {
+            let reached = self.fifo.discard == 0
+                && self.lcd_control & LCDC_OBJ_ENABLE != 0
+                && self
+                    .fifo
+                    .sprites
+                    .first()
+                    .is_some_and(|sprite| sprite.x <= self.fifo.lcd_x + 8);
+            if !reached {
+                return false;
+            }
+            let sprite = self.fifo.sprites.remove(0);
+            self.fifo.sprite_fetch = Some((sprite, 0));
+        }
+
+        // The sprite fetch only starts once the background fetcher has a tile ready to push.
+        if self.fifo.step != FetchStep::Push || self.fifo.bg.is_empty() {
+            return true;
+        }
+
+        let Some((sprite, dots)) = self.fifo.sprite_fetch.as_mut() else {
+            unreachable!();
+        };
+        *dots += 1;
+        if *dots < SPRITE_FETCH_DOTS {
+            return true;
+        }
+        let sprite = *sprite;
+        self.fifo.sprite_fetch = None;
+        self.merge_sprite(&sprite);
+        true
+    }
+
+    /*
+     * Mix a fetched sprite's row into the sprite FIFO. Pixels already held by an earlier sprite take priority.
+     */
+    fn merge_sprite(&mut self, sprite: &Sprite) {
+        let (tile_address, row) = self.sprite_tile_row(sprite);
+        let palette = self.obj_palette(sprite);
+        // Sprites partially off the left edge lose their leftmost columns.
+        let hidden = 8u8.saturating_sub(sprite.x);
+
+        while self.fifo.obj.len() < 8 {
+            self.fifo.obj.push_back(TRANSPARENT);
+        }
+        for column in hidden..8 {
+            let tile_column = if sprite.attributes & OBJ_X_FLIP != 0 {
+                7 - column
+            } else {
+                column
+            };
+            let color = self.tile_pixel(tile_address, tile_column, row);
+            let slot = &mut self.fifo.obj[(column - hidden) as usize];
+            if slot.color == 0 && color != 0 {
+                *slot = ObjPixel {
+                    color,
+                    palette,
+                    bg_priority: sprite.attributes & OBJ_BG_PRIORITY != 0,
+                };
+            }
+        }
+    }
+}
@@ -0,0 +1,393 @@
+mod fifo;
+mod scanline;
+
+use fifo::PixelFifo;
+
+use crate::io::Interrupt;
+
+pub(crate) const WIDTH: usize = 160;
+pub(crate) const HEIGHT: usize = 144;
+
+pub(crate) const VIDEO_RAM_SIZE: usize = 0x2000;
+pub(crate) const OAM_SIZE: usize = 0xA0;
+
+const DOTS_PER_LINE: u16 = 456;
+const OAM_SCAN_DOTS: u16 = 80;
+const DRAWING_DOTS: u16 = 172;
+const LINES_PER_FRAME: u8 = 154;
+const MAX_SPRITES_PER_LINE: usize = 10;
+
+// Lightest to darkest, as 0RGB for the minifb buffer.
+const SHADES: [u32; 4] = [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000];
+
+const LCDC_BG_ENABLE: u8 = 0b00000001;
+const LCDC_OBJ_ENABLE: u8 = 0b00000010;
+const LCDC_OBJ_SIZE: u8 = 0b00000100;
+const LCDC_BG_TILE_MAP: u8 = 0b00001000;
+const LCDC_TILE_DATA: u8 = 0b00010000;
+const LCDC_WINDOW_ENABLE: u8 = 0b00100000;
+const LCDC_WINDOW_TILE_MAP: u8 = 0b01000000;
+const LCDC_LCD_ENABLE: u8 = 0b10000000;
+
+const STAT_HBLANK_SELECT: u8 = 0b00001000;
+const STAT_VBLANK_SELECT: u8 = 0b00010000;
+const STAT_OAM_SELECT: u8 = 0b00100000;
+const STAT_LYC_SELECT: u8 = 0b01000000;
+
+const OBJ_BG_PRIORITY: u8 = 0b10000000;
+const OBJ_Y_FLIP: u8 = 0b01000000;
+const OBJ_X_FLIP: u8 = 0b00100000;
+const OBJ_PALETTE: u8 = 0b00010000;
+
+/*
+ * How pixels are produced during mode 3.
+ * Scanline draws each line in one go with a fixed mode 3 length, while PixelFifo models the background and sprite
+ * fetchers dot by dot, so mode 3 length varies and mid-line register writes take effect.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Renderer {
+    Scanline,
+    PixelFifo,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    HBlank,
+    VBlank,
+    OamScan,
+    Drawing,
+}
+
+#[derive(Clone, Copy)]
+struct Sprite {
+    y: u8,
+    x: u8,
+    tile: u8,
+    attributes: u8,
+}
+
+impl Mode {
+    fn bits(&self) -> u8 {
+        match self {
+            Mode::HBlank => 0,
+            Mode::VBlank => 1,
+            Mode::OamScan => 2,
+            Mode::Drawing => 3,
+        }
+    }
+}
+
+pub(crate) struct Ppu {
+    video_ram: [u8; VIDEO_RAM_SIZE],
+    oam: [u8; OAM_SIZE],
+    frame: Vec<u32>,
+    renderer: Renderer,
+    fifo: PixelFifo,
+
+    lcd_control: u8,
+    stat_select: u8,
+    scroll_y: u8,
+    scroll_x: u8,
+    ly: u8,
+    ly_compare: u8,
+    dma: u8,
+    bg_palette: u8,
+    obj_palette_0: u8,
+    obj_palette_1: u8,
+    window_y: u8,
+    window_x: u8,
+
+    mode: Mode,
+    dot: u16,
+    window_line: u8,
+    window_y_triggered: bool,
+    stat_line: bool,
+}
+
+impl Ppu {
+    pub(crate) fn new() -> Self {
+        let mut ppu = Self {
+            video_ram: [0; VIDEO_RAM_SIZE],
+            oam: [0; OAM_SIZE],
+            frame: vec![SHADES[0]; WIDTH * HEIGHT],
+            renderer: Renderer::Scanline,
+            fifo: PixelFifo::new(),
+            lcd_control: 0x91,
+            stat_select: 0,
+            scroll_y: 0,
+            scroll_x: 0,
+            ly: 0,
+            ly_compare: 0,
+            dma: 0xFF,
+            bg_palette: 0xFC,
+            obj_palette_0: 0,
+            obj_palette_1: 0,
+            window_y: 0,
+            window_x: 0,
+            mode: Mode::OamScan,
+            dot: 0,
+            window_line: 0,
+            window_y_triggered: false,
+            stat_line: false,
+        };
+        // The LCD starts out on, at the beginning of line 0's OAM scan.
+        ppu.start_line();
+        ppu
+    }
+
+    pub(crate) fn set_renderer(&mut self, renderer: Renderer) {
+        self.renderer = renderer;
+        // A line that hasn't started drawing yet is set up again for the new renderer.
+        if self.mode == Mode::OamScan {
+            self.start_line();
+        }
+    }
+
+    /*
+     * Advance the PPU by the given number of dots.
+     * Returns the IF bits of any interrupts requested along the way.
+     */
+    pub(crate) fn tick(&mut self, dots: u8) -> u8 {
+        if self.lcd_control & LCDC_LCD_ENABLE == 0 {
+            return 0;
+        }
+
+        let mut requested = 0;
+        for _ in 0..dots {
+            requested |= self.tick_dot();
+        }
+        requested
+    }
+
+    fn tick_dot(&mut self) -> u8 {
+        let mut requested = 0;
+
+        self.dot += 1;
+        if self.dot == DOTS_PER_LINE {
+            self.dot = 0;
+            self.ly += 1;
+            if self.ly == LINES_PER_FRAME {
+                self.ly = 0;
+                self.window_line = 0;
+            }
+        }
+
+        let drawing = match self.renderer {
+            Renderer::Scanline => self.dot < OAM_SCAN_DOTS + DRAWING_DOTS,
+            Renderer::PixelFifo => !self.fifo.line_done(),
+        };
+        let mode = if self.ly >= HEIGHT as u8 {
+            Mode::VBlank
+        } else if self.dot < OAM_SCAN_DOTS {
+            Mode::OamScan
+        } else if drawing {
+            Mode::Drawing
+        } else {
+            Mode::HBlank
+        };
+
+        if mode != self.mode {
+            match (mode, self.renderer) {
+                (Mode::OamScan, _) => self.start_line(),
+                (Mode::HBlank, Renderer::Scanline) => self.render_scanline(),
+                (Mode::HBlank, Renderer::PixelFifo) => self.end_fifo_line(),
+                (Mode::VBlank, _) => {
+                    self.window_y_triggered = false;
+                    requested |= Interrupt::VBlank.bit();
+                }
+                _ => {}
+            }
+            self.mode = mode;
+        }
+
+        if self.mode == Mode::Drawing && self.renderer == Renderer::PixelFifo {
+            self.tick_fifo();
+        }
+
+        if self.update_stat_line() {
+            requested |= Interrupt::Stat.bit();
+        }
+        requested
+    }
+
+    /*
+     * Set up for the line about to be drawn. WY is compared once per line here and latched until the next frame, so both
+     * renderers agree on where the window starts even if WY changes mid-frame.
+     */
+    fn start_line(&mut self) {
+        if self.ly == self.window_y {
+            self.window_y_triggered = true;
+        }
+        if self.renderer == Renderer::PixelFifo {
+            self.start_fifo_line();
+        }
+    }
+
+    /*
+     * The STAT interrupt fires on a rising edge of the OR of all selected conditions.
+     */
+    fn update_stat_line(&mut self) -> bool {
+        let line = (self.stat_select & STAT_HBLANK_SELECT != 0 && self.mode == Mode::HBlank)
+            || (self.stat_select & STAT_VBLANK_SELECT != 0 && self.mode == Mode::VBlank)
+            || (self.stat_select & STAT_OAM_SELECT != 0 && self.mode == Mode::OamScan)
+            || (self.stat_select & STAT_LYC_SELECT != 0 && self.ly == self.ly_compare);
+        let rising = line && !self.stat_line;
+        self.stat_line = line;
+        rising
+    }
+
+    pub(crate) fn frame(&self) -> &[u32] {
+        &self.frame
+    }
+
+    pub(crate) fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF40 => self.lcd_control,
+            0xFF41 => {
+                let coincidence = ((self.ly == self.ly_compare) as u8) << 2;
+                0x80 | self.stat_select | coincidence | self.mode.bits()
+            }
+            0xFF42 => self.scroll_y,
+            0xFF43 => self.scroll_x,
+            0xFF44 => self.ly,
+            0xFF45 => self.ly_compare,
+            0xFF46 => self.dma,
+            0xFF47 => self.bg_palette,
+            0xFF48 => self.obj_palette_0,
+            0xFF49 => self.obj_palette_1,
+            0xFF4A => self.window_y,
+            0xFF4B => self.window_x,
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn write(&mut self, address: u16, byte: u8) {
+        match address {
+            0xFF40 => {
+                let was_enabled = self.lcd_control & LCDC_LCD_ENABLE != 0;
+                self.lcd_control = byte;
+                if was_enabled && byte & LCDC_LCD_ENABLE == 0 {
+                    // Turning the LCD off resets LY and leaves the PPU idle in HBlank.
+                    self.ly = 0;
+                    self.dot = 0;
+                    self.window_line = 0;
+                    self.window_y_triggered = false;
+                    self.mode = Mode::HBlank;
+                    self.frame.fill(SHADES[0]);
+                } else if !was_enabled && byte & LCDC_LCD_ENABLE != 0 {
+                    // Turning it back on starts line 0 straight away.
+                    self.mode = Mode::OamScan;
+                    self.start_line();
+                }
+            }
+            0xFF41 => self.stat_select = byte & 0x78,
+            0xFF42 => self.scroll_y = byte,
+            0xFF43 => self.scroll_x = byte,
+            0xFF44 => {} // Read only
+            0xFF45 => self.ly_compare = byte,
+            0xFF46 => self.dma = byte,
+            0xFF47 => self.bg_palette = byte,
+            0xFF48 => self.obj_palette_0 = byte,
+            0xFF49 => self.obj_palette_1 = byte,
+            0xFF4A => self.window_y = byte,
+            0xFF4B => self.window_x = byte,
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn read_video_ram(&self, offset: u16) -> u8 {
+        self.video_ram[offset as usize]
+    }
+
+    pub(crate) fn write_video_ram(&mut self, offset: u16, byte: u8) {
+        self.video_ram[offset as usize] = byte;
+    }
+
+    pub(crate) fn read_oam(&self, offset: u16) -> u8 {
+        self.oam[offset as usize]
+    }
+
+    pub(crate) fn write_oam(&mut self, offset: u16, byte: u8) {
+        self.oam[offset as usize] = byte;
+    }
+
+    /*
+     * Offset into video RAM of a BG/window tile, following the LCDC addressing mode.
+     * $8000 mode indexes tiles unsigned from $8000; $8800 mode indexes them signed from $9000.
+     */
+    fn bg_tile_address(&self, index: u8) -> usize {
+        if self.lcd_control & LCDC_TILE_DATA != 0 {
+            index as usize * 16
+        } else {
+            (0x1000 + index as i8 as isize * 16) as usize
+        }
+    }
+
+    fn sprite_height(&self) -> u8 {
+        if self.lcd_control & LCDC_OBJ_SIZE != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    /*
+     * Select up to 10 sprites overlapping the current line, in OAM order.
+     */
+    fn scan_oam(&self) -> Vec<Sprite> {
+        let height = self.sprite_height() as u16;
+        let line = self.ly as u16 + 16;
+        self.oam
+            .chunks_exact(4)
+            .map(|entry| Sprite {
+                y: entry[0],
+                x: entry[1],
+                tile: entry[2],
+                attributes: entry[3],
+            })
+            .filter(|sprite| (sprite.y as u16..sprite.y as u16 + height).contains(&line))
+            .take(MAX_SPRITES_PER_LINE)
+            .collect()
+    }
+
+    /*
+     * Video RAM offset of a sprite's tile and the row of it on the current line, accounting for 8x16 mode and Y flip.
+     */
+    fn sprite_tile_row(&self, sprite: &Sprite) -> (usize, u8) {
+        let height = self.sprite_height();
+        let mut row = self.ly + 16 - sprite.y;
+        if sprite.attributes & OBJ_Y_FLIP != 0 {
+            row = height - 1 - row;
+        }
+        let tile = if height == 16 {
+            sprite.tile & 0xFE
+        } else {
+            sprite.tile
+        };
+        (tile as usize * 16, row)
+    }
+
+    fn obj_palette(&self, sprite: &Sprite) -> u8 {
+        if sprite.attributes & OBJ_PALETTE != 0 {
+            self.obj_palette_1
+        } else {
+            self.obj_palette_0
+        }
+    }
+
+    /*
+     * Colour index (0-3) of pixel (x, y) in the tile at the given video RAM offset.
+     * Rows beyond 7 continue into the following tile, as used by 8x16 objects.
+     */
+    fn tile_pixel(&self, tile_address: usize, x: u8, y: u8) -> u8 {
+        let row = tile_address + y as usize * 2;
+        let lower = self.video_ram[row];
+        let upper = self.video_ram[row + 1];
+        let bit = 7 - x;
+        (((upper >> bit) & 1) << 1) | ((lower >> bit) & 1)
+    }
+}
+
+fn shade(palette: u8, color: u8) -> u32 {
+    SHADES[((palette >> (color * 2)) & 0b11) as usize]
+}
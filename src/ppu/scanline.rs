@@ -0,0 +1,98 @@
+use super::*;
+
+impl Ppu {
+    /*
+     * Draw the current line into the frame in one go, using register values as they are at the end of mode 3.
+     */
+    pub(super) fn render_scanline(&mut self) {
+        let mut bg_colors = [0u8; WIDTH];
+        self.render_background(&mut bg_colors);
+        if self.lcd_control & LCDC_OBJ_ENABLE != 0 {
+            self.render_sprites(&bg_colors);
+        }
+    }
+
+    fn render_background(&mut self, bg_colors: &mut [u8; WIDTH]) {
+        let line = self.ly as usize * WIDTH;
+
+        // On DMG, clearing LCDC bit 0 blanks both the background and the window.
+        if self.lcd_control & LCDC_BG_ENABLE == 0 {
+            self.frame[line..line + WIDTH].fill(shade(self.bg_palette, 0));
+            return;
+        }
+
+        let window_visible = self.lcd_control & LCDC_WINDOW_ENABLE != 0
+            && self.window_y_triggered
+            && self.window_x <= 166;
+        let mut window_drawn = false;
+
+        for (x, bg_color) in bg_colors.iter_mut().enumerate() {
+            let color = if window_visible && x + 7 >= self.window_x as usize {
+                window_drawn = true;
+                self.tile_map_pixel(
+                    self.lcd_control & LCDC_WINDOW_TILE_MAP != 0,
+                    (x + 7 - self.window_x as usize) as u8,
+                    self.window_line,
+                )
+            } else {
+                self.tile_map_pixel(
+                    self.lcd_control & LCDC_BG_TILE_MAP != 0,
+                    self.scroll_x.wrapping_add(x as u8),
+                    self.scroll_y.wrapping_add(self.ly),
+                )
+            };
+            *bg_color = color;
+            self.frame[line + x] = shade(self.bg_palette, color);
+        }
+
+        // The window keeps its own line counter, which only advances on lines it was drawn on.
+        if window_drawn {
+            self.window_line += 1;
+        }
+    }
+
+    fn tile_map_pixel(&self, high_map: bool, x: u8, y: u8) -> u8 {
+        let map = if high_map { 0x1C00 } else { 0x1800 };
+        let index = self.video_ram[map + (y as usize / 8) * 32 + x as usize / 8];
+        self.tile_pixel(self.bg_tile_address(index), x % 8, y % 8)
+    }
+
+    fn render_sprites(&mut self, bg_colors: &[u8; WIDTH]) {
+        let mut sprites = self.scan_oam();
+        // On DMG the sprite with the smaller X wins, then the one earlier in OAM.
+        sprites.sort_by_key(|sprite| sprite.x);
+
+        let line = self.ly as usize * WIDTH;
+        let mut drawn = [false; WIDTH];
+        for sprite in sprites {
+            let (tile_address, row) = self.sprite_tile_row(&sprite);
+            let palette = self.obj_palette(&sprite);
+
+            for column in 0..8u8 {
+                let x = sprite.x as usize + column as usize;
+                if !(8..WIDTH + 8).contains(&x) || drawn[x - 8] {
+                    continue;
+                }
+                let x = x - 8;
+
+                let column = if sprite.attributes & OBJ_X_FLIP != 0 {
+                    7 - column
+                } else {
+                    column
+                };
+                let color = self.tile_pixel(tile_address, column, row);
+                if color == 0 {
+                    continue;
+                }
+
+                // An opaque pixel of a higher priority sprite hides lower priority sprites, even if the background then
+                // covers it.
+                drawn[x] = true;
+                if sprite.attributes & OBJ_BG_PRIORITY != 0 && bg_colors[x] != 0 {
+                    continue;
+                }
+                self.frame[line + x] = shade(palette, color);
+            }
+        }
+    }
+}
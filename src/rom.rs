@@ -0,0 +1,132 @@
+use sevenz_rust2::{ArchiveReader, Password};
+use std::fmt;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+use zip::ZipArchive;
+
+// Archives are recognised by their contents rather than their extension.
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const GZIP_MAGIC: &[u8] = b"\x1F\x8B";
+const SEVEN_ZIP_MAGIC: &[u8] = b"7z\xBC\xAF\x27\x1C";
+
+// What a ROM inside an archive is called.
+const ROM_EXTENSIONS: [&str; 2] = ["gb", "gbc"];
+
+#[derive(Debug)]
+pub(crate) enum LoadError {
+    Io(io::Error),
+    // The archive is damaged or uses something the decoder doesn't support.
+    Archive(String),
+    NoRom,
+    MultipleRoms(Vec<String>),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Archive(e) => write!(f, "bad archive: {}", e),
+            LoadError::NoRom => write!(f, "archive has no .gb or .gbc file"),
+            LoadError::MultipleRoms(names) => {
+                write!(f, "archive has more than one ROM: {}", names.join(", "))
+            }
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+/*
+ * Read a ROM image from a plain .gb/.gbc file, or from the one ROM in a .zip or .7z archive or a gzipped ROM.
+ */
+pub(crate) fn load(path: &Path) -> Result<Vec<u8>, LoadError> {
+    let data = std::fs::read(path)?;
+    if data.starts_with(ZIP_MAGIC) {
+        from_zip(data)
+    } else if data.starts_with(GZIP_MAGIC) {
+        from_gzip(&data)
+    } else if data.starts_with(SEVEN_ZIP_MAGIC) {
+        from_7z(data)
+    } else {
+        Ok(data)
+    }
+}
+
+/*
+ * Whether the data is one of the archive formats `load` unpacks.
+ */
+pub(crate) fn is_archive(data: &[u8]) -> bool {
+    [ZIP_MAGIC, GZIP_MAGIC, SEVEN_ZIP_MAGIC]
+        .iter()
+        .any(|magic| data.starts_with(magic))
+}
+
+fn from_zip(data: Vec<u8>) -> Result<Vec<u8>, LoadError> {
+    let archive_error = |e: zip::result::ZipError| LoadError::Archive(e.to_string());
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(archive_error)?;
+    let names = archive
+        .file_names()
+        .map(|name| name.map(|name| name.into_owned()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(archive_error)?;
+    let name = single_rom(names)?;
+
+    let mut rom = Vec::new();
+    archive
+        .by_name(&name)
+        .map_err(archive_error)?
+        .read_to_end(&mut rom)?;
+    Ok(rom)
+}
+
+/*
+ * A gzip stream holds a single file, so there is nothing to choose.
+ */
+fn from_gzip(data: &[u8]) -> Result<Vec<u8>, LoadError> {
+    let mut rom = Vec::new();
+    flate2::read::MultiGzDecoder::new(data).read_to_end(&mut rom)?;
+    Ok(rom)
+}
+
+fn from_7z(data: Vec<u8>) -> Result<Vec<u8>, LoadError> {
+    let archive_error = |e: sevenz_rust2::Error| LoadError::Archive(e.to_string());
+    let mut archive =
+        ArchiveReader::new(Cursor::new(data), Password::empty()).map_err(archive_error)?;
+    let names = archive
+        .archive()
+        .files
+        .iter()
+        .filter(|entry| !entry.is_directory())
+        .map(|entry| entry.name().to_string())
+        .collect();
+    let name = single_rom(names)?;
+    archive.read_file(&name).map_err(archive_error)
+}
+
+/*
+ * The one entry named like a ROM. Anything else in the archive (readmes, NFOs, box art) is ignored.
+ */
+fn single_rom(names: Vec<String>) -> Result<String, LoadError> {
+    let mut roms: Vec<String> = names
+        .into_iter()
+        .filter(|name| {
+            Path::new(name)
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    ROM_EXTENSIONS
+                        .iter()
+                        .any(|rom_extension| extension.eq_ignore_ascii_case(rom_extension))
+                })
+        })
+        .collect();
+    match roms.len() {
+        0 => Err(LoadError::NoRom),
+        1 => Ok(roms.remove(0)),
+        _ => Err(LoadError::MultipleRoms(roms)),
+    }
+}
@@ -0,0 +1,80 @@
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::memory::Memory;
+
+/*
+ * The `<rom>.sav` file holding a cartridge's battery-backed memory, in the same layout other emulators use: the RAM
+ * image, followed by a clock footer on carts with one.
+ */
+pub(crate) struct SaveFile {
+    path: PathBuf,
+    // What is on disk, to skip rewriting an unchanged save.
+    written: Option<Vec<u8>>,
+}
+
+impl SaveFile {
+    pub(crate) fn new(rom_path: &Path) -> Self {
+        Self {
+            path: rom_path.with_extension("sav"),
+            written: None,
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /*
+     * Load an existing save into the cartridge. A missing file just means a fresh save.
+     */
+    pub(crate) fn load(&mut self, memory: &mut Memory) -> io::Result<()> {
+        if memory.save_data().is_none() {
+            return Ok(());
+        }
+        match std::fs::read(&self.path) {
+            Ok(data) => {
+                memory.load_save_data(&data);
+                self.written = Some(data);
+                Ok(())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /*
+     * Write the cartridge's battery-backed memory out if it differs from what was last written, not counting the clock
+     * footer's timestamp. The file is replaced in one step so a crash mid-write can't leave a truncated save.
+     */
+    pub(crate) fn flush(&mut self, memory: &Memory) -> io::Result<()> {
+        let Some(data) = memory.save_data() else {
+            return Ok(());
+        };
+        if let Some(written) = &self.written {
+            if unchanged(written, &data, memory.save_timestamp()) {
+                return Ok(());
+            }
+        }
+
+        let temporary = self.path.with_extension("sav.tmp");
+        std::fs::write(&temporary, &data)?;
+        std::fs::rename(&temporary, &self.path)?;
+        self.written = Some(data);
+        Ok(())
+    }
+}
+
+/*
+ * Whether two saves hold the same data, ignoring the bytes in `timestamp`.
+ */
+fn unchanged(written: &[u8], data: &[u8], timestamp: Option<Range<usize>>) -> bool {
+    let timestamp = timestamp.unwrap_or_default();
+    written.len() == data.len()
+        && written
+            .iter()
+            .zip(data)
+            .enumerate()
+            .all(|(index, (written, byte))| written == byte || timestamp.contains(&index))
+}
@@ -0,0 +1,165 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const HEADER_SIZE: u32 = 44;
+const BITS_PER_SAMPLE: u16 = 16;
+// The RIFF size field counts everything after itself, which caps the data at just under 4 GiB.
+const MAX_DATA_SIZE: u32 = u32::MAX - (HEADER_SIZE - 8);
+
+/*
+ * Streams 16-bit PCM samples to a WAV file. The RIFF and data chunk sizes are patched in when the writer is finished.
+ */
+pub(crate) struct WavWriter {
+    file: BufWriter<File>,
+    channels: u16,
+    data_size: u32,
+}
+
+impl WavWriter {
+    pub(crate) fn create(path: &Path, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        let mut writer = Self {
+            file: BufWriter::new(File::create(path)?),
+            channels,
+            data_size: 0,
+        };
+        writer.write_header(sample_rate)?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self, sample_rate: u32) -> io::Result<()> {
+        let block_align = self.channels * BITS_PER_SAMPLE / 8;
+        let file = &mut self.file;
+        file.write_all(b"RIFF")?;
+        file.write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+        file.write_all(b"fmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        // Format 1 is integer PCM.
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&self.channels.to_le_bytes())?;
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&self.data_size.to_le_bytes())
+    }
+
+    /*
+     * Whether `samples` more samples fit without the file going over the RIFF size limit.
+     */
+    fn has_room(&self, samples: usize) -> bool {
+        samples as u64 * 2 <= (MAX_DATA_SIZE - self.data_size) as u64
+    }
+
+    /*
+     * Append samples in -1.0..=1.0, interleaved if there is more than one channel. Samples that would take the file
+     * over the size limit are refused without writing any of them.
+     */
+    pub(crate) fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        if !self.has_room(samples.len()) {
+            return Err(io::Error::other("WAV file is at its 4 GiB limit"));
+        }
+        for sample in samples {
+            let pcm = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.file.write_all(&pcm.to_le_bytes())?;
+        }
+        self.data_size += samples.len() as u32 * 2;
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let file = self.file.get_mut();
+        file.seek(SeekFrom::Start(4))?;
+        file.write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        file.seek(SeekFrom::Start(40))?;
+        file.write_all(&self.data_size.to_le_bytes())?;
+        file.flush()
+    }
+}
+
+/*
+ * A capture of the mixed stereo output to one file and, optionally, each channel in mono to `<name>.ch1.wav` through
+ * `<name>.ch4.wav` next to it.
+ */
+pub(crate) struct Recording {
+    path: PathBuf,
+    mixed: WavWriter,
+    channels: Option<[WavWriter; 4]>,
+}
+
+impl Recording {
+    pub(crate) fn start(path: &Path, sample_rate: u32, split_channels: bool) -> io::Result<Self> {
+        let mixed = WavWriter::create(path, 2, sample_rate)?;
+        let channels = if split_channels {
+            let [ch1, ch2, ch3, ch4] = [1, 2, 3, 4].map(|channel| {
+                WavWriter::create(
+                    &path.with_extension(format!("ch{}.wav", channel)),
+                    1,
+                    sample_rate,
+                )
+            });
+            Some([ch1?, ch2?, ch3?, ch4?])
+        } else {
+            None
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            mixed,
+            channels,
+        })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn splits_channels(&self) -> bool {
+        self.channels.is_some()
+    }
+
+    /*
+     * Whether another `write` of this many mixed samples fits. The mono channel files hold half as much, so the mixed
+     * file is always the first to fill up.
+     */
+    pub(crate) fn has_room(&self, mixed: usize) -> bool {
+        self.mixed.has_room(mixed)
+    }
+
+    pub(crate) fn write(&mut self, mixed: &[f32], channels: &[Vec<f32>; 4]) -> io::Result<()> {
+        self.mixed.write(mixed)?;
+        if let Some(writers) = &mut self.channels {
+            for (writer, samples) in writers.iter_mut().zip(channels) {
+                writer.write(samples)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> io::Result<()> {
+        self.mixed.finish()?;
+        for writer in self.channels.into_iter().flatten() {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/*
+ * First of `<rom>.wav`, `<rom>-1.wav`, `<rom>-2.wav`, ... that doesn't exist yet, so hotkey captures never overwrite
+ * each other.
+ */
+pub(crate) fn next_recording_path(rom_path: &Path) -> PathBuf {
+    let path = rom_path.with_extension("wav");
+    if !path.exists() {
+        return path;
+    }
+    let stem = rom_path.file_stem().unwrap_or_default().to_string_lossy();
+    (1..)
+        .map(|n| rom_path.with_file_name(format!("{}-{}.wav", stem, n)))
+        .find(|path| !path.exists())
+        .unwrap()
+}